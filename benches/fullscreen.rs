@@ -0,0 +1,64 @@
+//! Times `extract_fullscreen_child` on trees with thousands of nodes.
+//! Run with `cargo bench --bench fullscreen`.
+use serde_json::{json, Value};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use sway_overfocus::tree::extract_fullscreen_child;
+use swayipc_types::Node;
+
+/// A split with `width` children on each of `depth` levels, numbered depth-first from `id`.
+fn tree(id: &mut i64, width: usize, depth: usize) -> Value {
+    *id += 1;
+    let this = *id;
+    let nodes: Vec<Value> = match depth {
+        0 => Vec::new(),
+        _ => (0..width).map(|_| tree(id, width, depth - 1)).collect(),
+    };
+    let rect = json!({"x": 0, "y": 0, "width": 0, "height": 0});
+    json!({
+        "id": this, "name": null, "type": "con", "border": "none",
+        "current_border_width": 0, "layout": "splith", "percent": null,
+        "rect": rect, "window_rect": rect, "deco_rect": rect, "geometry": rect,
+        "urgent": false, "focused": false, "focus": [],
+        "nodes": nodes, "floating_nodes": [], "sticky": false,
+    })
+}
+
+/// Average time of `f` over enough runs to take a fifth of a second.
+/// Each run is given a fresh input from `setup`, which isn't timed.
+fn time<T>(mut setup: impl FnMut() -> T, mut f: impl FnMut(&mut T)) -> Duration {
+    let (mut total, mut runs) = (Duration::ZERO, 0);
+    while total < Duration::from_millis(200) {
+        let mut input = setup();
+        let start = Instant::now();
+        f(&mut input);
+        total += start.elapsed();
+        runs += 1;
+    }
+    total / runs
+}
+
+fn main() {
+    println!("nodes\tfullscreen\ttime");
+    for depth in [5, 6, 7] {
+        let mut count = 0;
+        let value = tree(&mut count, 4, depth);
+        let mut tree: Node = serde_json::from_value(value).unwrap();
+        let extract = |t: &mut Node| {
+            black_box(extract_fullscreen_child(black_box(t)));
+        };
+
+        // Without a fullscreen container, every node is visited
+        let none = time(|| tree.clone(), extract);
+        println!("{count}\tnone\t{none:?}");
+
+        // The deepest last container is visited last
+        let mut last = &mut tree;
+        while let Some(child) = last.nodes.last_mut() {
+            last = child;
+        }
+        last.fullscreen_mode = Some(1);
+        let last = time(|| tree.clone(), extract);
+        println!("{count}\tlast\t{last:?}");
+    }
+}
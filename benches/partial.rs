@@ -0,0 +1,68 @@
+//! Times `parse_tree_partial` against a full parse, on replies with many workspaces.
+//! Run with `cargo bench --bench partial`.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use sway_overfocus::partial::parse_tree_partial;
+use swayipc_types::Node;
+
+/// A node serialized like sway does, with the ID and type first.
+fn node(id: i64, node_type: &str, nodes: &[String]) -> String {
+    let rect = r#"{"x":0,"y":0,"width":0,"height":0}"#;
+    format!(
+        r#"{{"id":{id},"type":"{node_type}","name":null,"border":"none","current_border_width":0,"layout":"splith","percent":null,"rect":{rect},"window_rect":{rect},"deco_rect":{rect},"geometry":{rect},"urgent":false,"focused":false,"focus":[],"nodes":[{}],"floating_nodes":[],"sticky":false}}"#,
+        nodes.join(",")
+    )
+}
+
+/// A split with `width` children on each of `depth` levels, numbered depth-first from `id`.
+fn split(id: &mut i64, width: usize, depth: usize) -> String {
+    *id += 1;
+    let this = *id;
+    let nodes: Vec<String> = match depth {
+        0 => Vec::new(),
+        _ => (0..width).map(|_| split(id, width, depth - 1)).collect(),
+    };
+    node(this, "con", &nodes)
+}
+
+/// A reply with one output holding `count` workspaces, each with 85 containers.
+/// Returns the reply and the ID of the first workspace.
+fn reply(count: usize) -> (String, i64) {
+    let mut id = 1;
+    let mut first = None;
+    let workspaces: Vec<String> = (0..count)
+        .map(|_| {
+            id += 1;
+            let workspace = id;
+            first.get_or_insert(workspace);
+            node(workspace, "workspace", &[split(&mut id, 4, 3)])
+        })
+        .collect();
+    let output = node(1, "output", &workspaces);
+    (node(0, "root", &[output]), first.unwrap())
+}
+
+/// Average time of `f` over enough runs to take a fifth of a second.
+fn time(mut f: impl FnMut()) -> Duration {
+    let (mut runs, start) = (0, Instant::now());
+    while start.elapsed() < Duration::from_millis(200) {
+        f();
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+fn main() {
+    println!("workspaces\tbytes\tfull\tpartial");
+    for count in [1, 10, 50] {
+        let (payload, workspace) = reply(count);
+        let payload = payload.as_bytes();
+        let full = time(|| {
+            black_box(serde_json::from_slice::<Node>(black_box(payload)).unwrap());
+        });
+        let partial = time(|| {
+            black_box(parse_tree_partial(black_box(payload), workspace).unwrap());
+        });
+        println!("{count}\t{}\t{full:?}\t{partial:?}", payload.len());
+    }
+}
@@ -1,34 +1,123 @@
 //! Neighbor-finding algorithm.
-use crate::tree::{closest_point, focus_idx, focus_local, Vec2};
-use log::{debug, trace, warn};
-use swayipc::{Node, NodeLayout, NodeType, Rect};
+use crate::tree::{
+    app, center, closest_point, focus_idx, focus_local, focused_leaf, leaves, visible_leaves, Vec2,
+};
+use log::{debug, info, log_enabled, trace, warn, Level};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use swayipc_types::{Node, NodeLayout, NodeType, Rect, Workspace};
 
 /// A target description for neighbor searching.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// When deserializing, all fields other than `kind` default to those of [Target::new].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Target {
     /// The kind of neighbor to find.
     pub kind: Kind,
     /// Whether to find the succeeding or preceding neighbor.
+    #[serde(default)]
     pub backward: bool,
     /// Whether to search horizontally or vertically.
+    #[serde(default)]
     pub vertical: bool,
     /// Moving-past-edge behavior.
+    #[serde(default)]
     pub edge_mode: EdgeMode,
+    /// Containers with fewer children than this are not matched.
+    #[serde(default)]
+    pub min_children: usize,
+    /// Visit at most one window per application, skipping that of the focused window.
+    /// Only applies to `recent`, `created`, and `newest` targets.
+    #[serde(default)]
+    pub distinct_apps: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+impl Target {
+    /// Create a target of `kind` that moves right and stops at the edge.
+    /// The other settings can be changed by chaining the methods below.
+    pub fn new(kind: Kind) -> Self {
+        Target {
+            kind,
+            backward: false,
+            vertical: false,
+            edge_mode: EdgeMode::Stop,
+            min_children: 0,
+            distinct_apps: false,
+        }
+    }
+
+    /// Set whether to find the preceding neighbor.
+    pub fn backward(self, backward: bool) -> Self {
+        Target { backward, ..self }
+    }
+
+    /// Set whether to search vertically.
+    pub fn vertical(self, vertical: bool) -> Self {
+        Target { vertical, ..self }
+    }
+
+    /// Set the moving-past-edge behavior.
+    pub fn edge_mode(self, edge_mode: EdgeMode) -> Self {
+        Target { edge_mode, ..self }
+    }
+
+    /// Set the number of children below which containers aren't matched.
+    pub fn min_children(self, min_children: usize) -> Self {
+        Target {
+            min_children,
+            ..self
+        }
+    }
+
+    /// Set whether to skip windows of the same application as the focused one.
+    pub fn distinct_apps(self, distinct_apps: bool) -> Self {
+        Target {
+            distinct_apps,
+            ..self
+        }
+    }
+}
+
+/// Kinds are named as in targets when serialized, fx. `float-any`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Kind {
     Split,
     Group,
     Float,
     Workspace,
     Output,
+    /// Workspaces without any windows.
+    Empty,
+    /// Toggle between the tiling and floating layer of a workspace.
+    Layer,
+    /// Visible tiled and floating windows of a workspace, compared by geometry.
+    Window,
+    /// The nearest float in any direction.
+    FloatAny,
+    /// Jump between a window and its dialog.
+    Dialog,
+    /// The largest tiled window of a workspace.
+    Master,
+    /// The most recently focused tiled window of a workspace, other than the master.
+    Stack,
+    /// The most recently focused window of a workspace, other than the focused one.
+    Recent,
+    /// Windows of a workspace in order of creation, approximated by container ID.
+    Created,
+    /// The most recently created window anywhere, approximated by container ID.
+    Newest,
+    /// The most recently focused output other than the focused one.
+    OutputPrev,
+    /// Urgent windows, ordered by workspace across all outputs.
+    Urgent,
 }
 
 /// Describes what to do when attempting to move past the last or first child of a container.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EdgeMode {
     /// Do nothing, don't change focus.
+    #[default]
     Stop,
     /// Wrap around and focus the first or last child.
     Wrap,
@@ -38,46 +127,157 @@ pub enum EdgeMode {
     Inactive,
 }
 
+/// Options that apply to all targets of a search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    /// Only consider floats whose near edge is past the far edge of the focused float,
+    /// rather than floats whose center is past the center of the focused float.
+    pub strict_float: bool,
+    /// Search the focus path from the root downward, rather than from the focused node upward.
+    pub outermost: bool,
+    /// Find neighbors of the parent of the focused node, rather than the node itself.
+    pub from_parent: bool,
+    /// Consider floats on all visible workspaces for float targets, rather than only those on
+    /// the focused workspace.
+    pub visible_floats: bool,
+    /// Let window and float targets select windows that aren't visible,
+    /// such as hidden tabs and floats on hidden workspaces.
+    pub include_hidden: bool,
+}
+
 /// Find a neighbor matching one of the `targets`.
-pub fn neighbor<'a>(mut t: &'a Node, targets: &[Target]) -> Option<&'a Node> {
+pub fn neighbor<'a>(t: &'a Node, targets: &[Target], options: &Options) -> Option<&'a Node> {
+    search(t, targets, options).neighbor
+}
+
+/// The outcome of a search for a neighbor.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Search<'a> {
+    /// The ancestor on the focus path that ended the search, either by containing the neighbor
+    /// or by stopping at its edge.
+    pub ancestor: Option<&'a Node>,
+    /// The neighbor matching one of the targets, if any.
+    pub neighbor: Option<&'a Node>,
+}
+
+/// Search for a neighbor matching one of the `targets`, like [neighbor],
+/// but also report the ancestor that the neighbor was found within.
+pub fn search<'a>(mut t: &'a Node, targets: &[Target], options: &Options) -> Search<'a> {
+    let root = t;
     // Generate the focus path as a list of ancestors
-    debug!("Finding focus path");
     let mut path = Vec::new();
+    let mut complete = true;
     while !t.focused {
-        debug!("Node {}", t.id);
         path.push(t);
         if let Some(new_t) = focus_local(t) {
             t = new_t;
         } else {
             warn!("No focused child, incomplete focus path");
+            complete = false;
             break;
         }
     }
-    debug!("Searching focus path bottom-up for neighbor");
-    let neighbor = path.iter().rev().find_map(|parent| {
+    let logging = log_enabled!(Level::Info);
+    if logging {
+        let ids: Vec<i64> = path.iter().map(|n| n.id).collect();
+        info!(path:serde = ids, complete; "Found focus path");
+    }
+    if options.from_parent {
+        debug!("Searching from parent, skipping innermost ancestor");
+        path.pop();
+    }
+    if options.outermost {
+        debug!("Searching focus path top-down for neighbor");
+    } else {
+        debug!("Searching focus path bottom-up for neighbor");
+        path.reverse();
+    }
+    // The ancestors that matched a target, along with the neighbor found within each,
+    // only collected when they will be logged
+    let mut candidates = Vec::new();
+    let search = path.iter().find_map(|parent| {
         debug!("Parent {}", parent.id);
         let target = match_targets(parent, targets)?;
         trace!("Matched {target:?}");
-        let n = neighbor_local(parent, &target);
+        let n = neighbor_local(root, parent, &target, options);
+        if logging {
+            candidates.push(serde_json::json!({
+                "ancestor": parent.id,
+                "target": target,
+                "neighbor": n.map(|n| n.id),
+            }));
+        }
         if target.edge_mode == EdgeMode::Stop {
             debug!("Target is stopping, forcing return");
-            Some(n) // `Some(None)` can stop the search without a result
+            Some((parent, n)) // `n = None` can stop the search without a result
         } else {
-            n.map(Some)
+            Some((parent, Some(n?)))
         }
-    })??;
+    });
+    if logging {
+        info!(candidates:serde = candidates; "Searched focus path");
+    }
+    let (ancestor, neighbor) = match search {
+        Some((ancestor, n)) => (ancestor, n),
+        // Nothing is focused, so there's nothing to be relative to
+        None if !complete => {
+            debug!(
+                "No neighbor found, falling back to first window of {}",
+                t.id
+            );
+            let neighbor = first_window(t);
+            return Search {
+                ancestor: None,
+                neighbor,
+            };
+        }
+        None => return Search::default(),
+    };
+    let Some(neighbor) = neighbor else {
+        return Search {
+            ancestor: Some(ancestor),
+            neighbor: None,
+        };
+    };
     debug!("Found neighbor {}, selecting descendant", neighbor.id);
-    Some(select_leaf(neighbor, targets))
+    Search {
+        ancestor: Some(ancestor),
+        neighbor: Some(select_leaf(neighbor, targets, options)),
+    }
+}
+
+/// Find the innermost container on the focus path whose children match one of the `targets`.
+pub fn matching_ancestor<'a>(mut t: &'a Node, targets: &[Target]) -> Option<&'a Node> {
+    let mut res = None;
+    while !t.focused {
+        if match_targets(t, targets).is_some() {
+            res = Some(t);
+        }
+        let Some(child) = focus_local(t) else { break };
+        t = child;
+    }
+    res
+}
+
+/// Descend to the first window under `node`, ignoring focus.
+fn first_window(mut node: &Node) -> Option<&Node> {
+    while let Some(child) = node.nodes.first().or(node.floating_nodes.first()) {
+        node = child;
+    }
+    matches!(node.node_type, NodeType::Con | NodeType::FloatingCon).then_some(node)
 }
 
 /// Finds a parent that contains direct children matching one of the `targets`.
 fn match_targets(node: &Node, targets: &[Target]) -> Option<Target> {
-    let focus = *node.focus.first()?;
-    let float_focused = node.floating_nodes.iter().any(|c| c.id == focus);
+    let (_, children) = focus_idx(node)?;
+    let float_focused = std::ptr::eq(children, &node.floating_nodes);
     let res = *targets.iter().find(|target| match target.kind {
         // Note that we match with a suitable _parent type_ for the target
-        Kind::Output => node.node_type == NodeType::Root,
-        Kind::Workspace => node.node_type == NodeType::Output,
+        Kind::Output | Kind::Newest | Kind::OutputPrev | Kind::Urgent => {
+            node.node_type == NodeType::Root
+        }
+        Kind::Workspace | Kind::Empty => node.node_type == NodeType::Output,
         Kind::Split => {
             !float_focused
                 && (!target.vertical && node.layout == NodeLayout::SplitH
@@ -88,31 +288,165 @@ fn match_targets(node: &Node, targets: &[Target]) -> Option<Target> {
                 && (!target.vertical && node.layout == NodeLayout::Tabbed
                     || target.vertical && node.layout == NodeLayout::Stacked)
         }
-        Kind::Float => float_focused,
-    })?;
+        Kind::Float | Kind::FloatAny => float_focused,
+        Kind::Layer
+        | Kind::Window
+        | Kind::Dialog
+        | Kind::Master
+        | Kind::Stack
+        | Kind::Recent
+        | Kind::Created => node.node_type == NodeType::Workspace,
+    } && children.len() >= target.min_children)?;
     Some(res)
 }
 
 /// Attempt to find a neighbor of the focused child `node`,
 /// according to the given target.
-fn neighbor_local<'a>(node: &'a Node, target: &Target) -> Option<&'a Node> {
+/// The `root` of the tree is used for targets that look beyond `node`.
+fn neighbor_local<'a>(
+    root: &'a Node,
+    node: &'a Node,
+    target: &Target,
+    options: &Options,
+) -> Option<&'a Node> {
     let (focus_idx, children) = focus_idx(node)?;
 
-    if target.kind == Kind::Float || target.kind == Kind::Output {
-        let focus_id = *node.focus.first()?;
+    if target.kind == Kind::Layer {
+        let origin = focused_leaf(&children[focus_idx]).rect;
+        trace!("Selecting nearest node in other layer of {:?}", origin);
+        if std::ptr::eq(children, &node.floating_nodes) {
+            nearest(&origin, node.nodes.iter().flat_map(visible_leaves))
+        } else {
+            nearest(&origin, node.floating_nodes.iter())
+        }
+    } else if target.kind == Kind::Dialog {
+        let focused = focused_leaf(&children[focus_idx]);
+        trace!("Selecting dialog or main window of {}", focused.id);
+        dialog_partner(node, focused)
+    } else if matches!(target.kind, Kind::Master | Kind::Stack) {
+        let focused = focused_leaf(&children[focus_idx]);
+        let master = master(node)?;
+        let res = match target.kind {
+            Kind::Master => master,
+            _ => recent_leaf(node, master.id)?,
+        };
+        trace!("Master is {}, selected {}", master.id, res.id);
+        (res.id != focused.id).then_some(res)
+    } else if target.kind == Kind::Recent {
+        let focused = focused_leaf(&children[focus_idx]);
+        trace!("Selecting most recent window other than {}", focused.id);
+        let recent = distinct_apps(target, focused, recent_leaves(node));
+        recent.into_iter().find(|n| n.id != focused.id)
+    } else if target.kind == Kind::Created {
+        let focused_leaf = focused_leaf(&children[focus_idx]);
+        let focused = focused_leaf.id;
+        let mut windows: Vec<&Node> = leaves(node)
+            .into_iter()
+            .filter(|n| matches!(n.node_type, NodeType::Con | NodeType::FloatingCon))
+            .collect();
+        // The oldest window of each app represents it
+        windows.sort_by_key(|n| n.id);
+        let windows = distinct_apps(target, focused_leaf, windows);
+        trace!("Selecting window created after or before {focused}");
+        let (newer, older): (Vec<&Node>, Vec<&Node>) = windows
+            .into_iter()
+            .filter(|n| n.id != focused)
+            .partition(|n| n.id > focused);
+        let key = |n: &&Node| n.id;
+        let wrap = target.edge_mode == EdgeMode::Wrap;
+        if target.backward {
+            let res = older.into_iter().max_by_key(key);
+            res.or_else(|| wrap.then(|| newer.into_iter().max_by_key(key))?)
+        } else {
+            let res = newer.into_iter().min_by_key(key);
+            res.or_else(|| wrap.then(|| older.into_iter().min_by_key(key))?)
+        }
+    } else if target.kind == Kind::Newest {
+        let focused_leaf = focused_leaf(&children[focus_idx]);
+        let focused = focused_leaf.id;
+        let windows: Vec<&Node> = leaves(node)
+            .into_iter()
+            .filter(|n| matches!(n.node_type, NodeType::Con | NodeType::FloatingCon))
+            .collect();
+        let newest = distinct_apps(target, focused_leaf, windows)
+            .into_iter()
+            .max_by_key(|n| n.id)?;
+        trace!("Newest window is {}", newest.id);
+        (newest.id != focused).then_some(newest)
+    } else if target.kind == Kind::OutputPrev {
+        let focused = children[focus_idx].id;
+        trace!("Selecting most recently focused output other than {focused}");
+        let mut recent = node.focus.iter().filter(|id| **id != focused);
+        recent.find_map(|id| children.iter().find(|n| n.id == *id))
+    } else if target.kind == Kind::Urgent {
+        let focused = focus_local(&children[focus_idx])?.id;
+        let workspaces: Vec<&Node> = children.iter().flat_map(|o| &o.nodes).collect();
+        let len = workspaces.len() as isize;
+        let pos = workspaces.iter().position(|w| w.id == focused)? as isize;
+        trace!("Selecting nearest workspace with urgent windows");
+        (1..len)
+            .map_while(|step| {
+                let idx = pos + if target.backward { -step } else { step };
+                if target.edge_mode == EdgeMode::Wrap {
+                    Some(idx.rem_euclid(len))
+                } else {
+                    (0..len).contains(&idx).then_some(idx)
+                }
+            })
+            .map(|idx| workspaces[idx as usize])
+            .find_map(|w| leaves(w).into_iter().find(|n| n.urgent))
+    } else if target.kind == Kind::FloatAny {
         let focused = &children[focus_idx];
+        trace!("Selecting nearest float to {:?}", focused.rect);
+        nearest(
+            &focused.rect,
+            children.iter().filter(|n| n.id != focused.id),
+        )
+    } else if matches!(target.kind, Kind::Float | Kind::Output | Kind::Window) {
+        // Windows are compared with all visible leaves, other kinds with their siblings
+        let (focused, children) = if target.kind == Kind::Window {
+            let candidates = window_candidates(node, options.include_hidden);
+            (focused_leaf(&children[focus_idx]), candidates)
+        } else if target.kind == Kind::Float && options.visible_floats {
+            (
+                &children[focus_idx],
+                global_floats(root, options.include_hidden),
+            )
+        } else {
+            (&children[focus_idx], children.iter().collect())
+        };
+        let focus_id = focused.id;
         trace!("Focused {:?}", focused.rect);
+        let graph = (target.kind == Kind::Output).then(|| OutputGraph::new(&children));
+
+        // Position of a node in the focus stack, lower is more recent.
+        let recency = |t: &Node| {
+            let pos = node.focus.iter().position(|id| *id == t.id);
+            pos.unwrap_or(node.focus.len()) as i64
+        };
 
-        // Selects x or y component of a rect based on whether target is horizontal or vertical
-        let component = |r: &Rect| if target.vertical { (r.y, r.height) } else { (r.x, r.width) };
+        // Squared euclidean distance from the center of the focused node to `t`.
+        let euclidean = |t: &Node| {
+            let c = Vec2 {
+                x: focused.rect.x + focused.rect.width / 2,
+                y: focused.rect.y + focused.rect.height / 2,
+            };
+            let p = closest_point(&t.rect, &c);
+            (c.x - p.x) * (c.x - p.x) + (c.y - p.y) * (c.y - p.y)
+        };
 
         // Computes a distance to the focused node.
         // Handles directions and filters out irrelevant neighbors.
-        let dist = |t: &Node, flip: bool| -> Option<(i32, i64)> {
+        // The second component breaks ties in favor of the most recently focused node,
+        // both when minimizing and when maximizing (`wrap`).
+        let dist = |t: &Node, flip: bool, wrap: bool| -> Option<(i32, i64)> {
             trace!("Computing distance to {}", t.id);
-            if t.id == focus_id { return None; }
+            if t.id == focus_id {
+                return None;
+            }
             let (a, b) = if flip { (&t.rect, &focused.rect) } else { (&focused.rect, &t.rect) };
-            let ((a_pos, a_dim), (b_pos, b_dim)) = (component(a), component(b));
+            let (a_pos, a_dim) = component(a, target.vertical);
+            let (b_pos, b_dim) = component(b, target.vertical);
             let (a_mid, b_mid) = (a_pos + a_dim / 2, b_pos + b_dim / 2);
             let a_edge = a_pos + a_dim;
             trace!("A-component: ({a_pos}, {a_dim}), B-component: ({b_pos}, {b_dim})");
@@ -120,42 +454,107 @@ fn neighbor_local<'a>(node: &'a Node, target: &Target) -> Option<&'a Node> {
 
             let dist = match target.kind {
                 // Floats are compared by distance of centers on relevant axis
-                Kind::Float if a_mid < b_mid || (a_mid == b_mid && flip == (t.id > focus_id)) => {
+                Kind::Float if options.strict_float && a_edge <= b_pos => {
                     Some((b_mid - a_mid).saturating_abs())
                 }
-                // Outputs are compared by euclidean distance to center of focused node
-                Kind::Output if a_edge <= b_pos => {
-                    let c = Vec2 {
-                        x: focused.rect.x + focused.rect.width / 2,
-                        y: focused.rect.y + focused.rect.height / 2,
-                    };
-                    let p = closest_point(&t.rect, &c);
-                    Some((c.x - p.x) * (c.x - p.x) + (c.y - p.y) * (c.y - p.y))
+                Kind::Float
+                    if !options.strict_float
+                        && (a_mid < b_mid || (a_mid == b_mid && flip == (t.id > focus_id))) =>
+                {
+                    Some((b_mid - a_mid).saturating_abs())
                 }
+                // Outputs are compared by euclidean distance to center of focused node,
+                // and so are windows, though they only have to be centered past the focused one
+                Kind::Output if a_edge <= b_pos => Some(euclidean(t)),
+                Kind::Window if a_mid < b_mid => Some(euclidean(t)),
                 _ => None,
             }?;
             trace!("Distance: {dist}");
-            Some((dist, if flip { t.id } else { -t.id }))
+            // Nodes centered on the focused node are ordered by ID instead,
+            // so that cycling through a pile of them visits every node.
+            // Ordering them by recency would alternate between the two most recent ones,
+            // as focusing one makes the previously focused one the most recent.
+            let tie = if dist == 0 {
+                if flip {
+                    t.id
+                } else {
+                    -t.id
+                }
+            } else if wrap {
+                -recency(t)
+            } else {
+                recency(t)
+            };
+            Some((dist, tie))
         };
+        // Outputs move to an adjacent output if there is one,
+        // and otherwise to the nearest one past the edge, fx. across a gap.
+        let adjacent = graph
+            .as_ref()
+            .map(|g| g.adjacent(focus_id, target.backward, target.vertical))
+            .filter(|adjacent| !adjacent.is_empty());
         // Select the closest neighbor to focused child,
         // or furthest in the opposite direction if wrapping.
-        let mut res = children
+        let mut res = adjacent
+            .unwrap_or(&children)
             .iter()
-            .filter_map(|n| Some((dist(n, target.backward)?, n)))
+            .copied()
+            .filter_map(|n| Some((dist(n, target.backward, false)?, n)))
             .min_by_key(|(d, _)| *d)
             .map(|(_, node)| node);
+        // With [EdgeMode::Inactive], spilling over onto the floats of another output
+        // selects the most recently focused float in the direction there.
+        // Outputs need no special case, as their inactive focus is selected on descent.
+        if let (EdgeMode::Inactive, Kind::Float, Some(found)) = (target.edge_mode, target.kind, res)
+        {
+            let workspaces = root.nodes.iter().flat_map(|o| &o.nodes);
+            let mut workspaces = workspaces.filter(|w| w.id != node.id);
+            if let Some(w) = workspaces.find(|w| w.floating_nodes.iter().any(|n| n.id == found.id))
+            {
+                trace!("Spilling over onto floats of workspace {}", w.id);
+                let floats = w
+                    .focus
+                    .iter()
+                    .filter_map(|id| w.floating_nodes.iter().find(|n| n.id == *id));
+                let mut floats = floats.filter(|n| dist(n, target.backward, false).is_some());
+                res = floats.next().or(res);
+            }
+        }
         if res.is_none() && target.edge_mode == EdgeMode::Wrap {
             trace!("No neighbor, searching for wraparound target");
-            let wrap_target = children
-                .iter()
-                .filter_map(|n| Some((dist(n, !target.backward)?, n)))
-                .max_by_key(|(d, _)| *d)
-                .map(|(_, node)| node);
+            // Outputs wrap around to the last output adjacent in the opposite direction,
+            // fx. the left-most output in the row of the focused one
+            let last = graph
+                .as_ref()
+                .and_then(|g| g.last(focused, !target.backward, target.vertical));
+            let wrap_target = last.or_else(|| {
+                children
+                    .iter()
+                    .copied()
+                    .filter_map(|n| Some((dist(n, !target.backward, true)?, n)))
+                    .max_by_key(|(d, _)| *d)
+                    .map(|(_, node)| node)
+            });
             // Also include focused container as a last resort.
             // This allows nice interaction between [EdgeMode::Traverse] and [EdgeMode::Wrap].
             res = wrap_target.or(Some(focused));
         }
         res
+    } else if target.kind == Kind::Empty {
+        trace!("Selecting nearest empty workspace");
+        let len = children.len() as isize;
+        (1..len)
+            .map_while(|step| {
+                let idx = focus_idx as isize + if target.backward { -step } else { step };
+                if target.edge_mode == EdgeMode::Wrap {
+                    Some(idx.rem_euclid(len))
+                } else {
+                    (0..len).contains(&idx).then_some(idx)
+                }
+            })
+            .map(|idx| &children[idx as usize])
+            // Workspaces list all of their children in their focus array
+            .find(|n| n.focus.is_empty())
     } else {
         trace!("Selecting neighbor by index");
         let len = children.len();
@@ -177,8 +576,255 @@ fn neighbor_local<'a>(node: &'a Node, target: &Target) -> Option<&'a Node> {
     }
 }
 
-/// Find a leaf in a (presumed) neighboring container, respecting target edge-modes
-fn select_leaf<'a>(mut t: &'a Node, targets: &[Target]) -> &'a Node {
+/// Select the `n`th visible window of the focused workspace, counting from 1.
+/// Windows are ordered left-to-right, then top-to-bottom.
+pub fn nth_window(mut t: &Node, n: usize) -> Option<&Node> {
+    while t.node_type != NodeType::Workspace {
+        t = focus_local(t)?;
+    }
+    let mut windows = window_candidates(t, false);
+    windows.sort_by_key(|w| (w.rect.x, w.rect.y, w.id));
+    windows.get(n.checked_sub(1)?).copied()
+}
+
+/// Compute the position that `float` snaps to when moved in a direction within the workspace `ws`.
+/// The float is moved until its leading edge aligns with the nearest edge of the workspace,
+/// or of another float beside it. Returns the new position along the axis of movement.
+pub fn snap_position(ws: &Node, float: &Node, backward: bool, vertical: bool) -> Option<i32> {
+    let along = |r: &Rect| if vertical { (r.y, r.height) } else { (r.x, r.width) };
+    let across = |r: &Rect| if vertical { (r.x, r.width) } else { (r.y, r.height) };
+    let (pos, dim) = along(&float.rect);
+    let (a_pos, a_dim) = across(&float.rect);
+    let beside = |n: &&Node| {
+        let (b_pos, b_dim) = across(&n.rect);
+        n.id != float.id && a_pos < b_pos + b_dim && b_pos < a_pos + a_dim
+    };
+    let floats = ws.floating_nodes.iter().filter(beside);
+    let spans = std::iter::once(along(&ws.rect)).chain(floats.map(|n| along(&n.rect)));
+    let edges = spans.flat_map(|(p, d)| [p, p + d]);
+    if backward {
+        edges.filter(|e| *e < pos).max()
+    } else {
+        edges.map(|e| e - dim).filter(|p| *p > pos).min()
+    }
+}
+
+/// Find the topmost visible window containing the point `p`, on any output.
+/// Floats are above tiled windows, with later floats above earlier ones.
+pub fn window_at<'a>(root: &'a Node, p: &Vec2) -> Option<&'a Node> {
+    let contains = |n: &&Node| {
+        let r = &n.rect;
+        (r.x..r.x + r.width).contains(&p.x) && (r.y..r.y + r.height).contains(&p.y)
+    };
+    root.nodes.iter().filter_map(focus_local).find_map(|ws| {
+        let floats = ws.floating_nodes.iter().rev().flat_map(visible_leaves);
+        let tiled = ws.nodes.iter().flat_map(visible_leaves);
+        floats.chain(tiled).find(contains)
+    })
+}
+
+/// Collect the floats of the visible workspace of every output,
+/// or of all workspaces if `include_hidden` is set.
+fn global_floats(root: &Node, include_hidden: bool) -> Vec<&Node> {
+    if include_hidden {
+        let workspaces = root.nodes.iter().flat_map(|o| &o.nodes);
+        return workspaces.flat_map(|w| &w.floating_nodes).collect();
+    }
+    let workspaces = root.nodes.iter().filter_map(focus_local);
+    let floats = workspaces.flat_map(|w| &w.floating_nodes);
+    floats.filter(|n| n.visible != Some(false)).collect()
+}
+
+/// Collect the visible leaves of a workspace, both tiled and floating,
+/// or all leaves if `include_hidden` is set.
+fn window_candidates(workspace: &Node, include_hidden: bool) -> Vec<&Node> {
+    if include_hidden {
+        return leaves(workspace);
+    }
+    let tiled = workspace.nodes.iter().flat_map(visible_leaves);
+    let floating = workspace.floating_nodes.iter().flat_map(visible_leaves);
+    tiled
+        .chain(floating)
+        .filter(|n| n.visible != Some(false))
+        .collect()
+}
+
+/// Select the largest tiled window of the workspace `ws`,
+/// preferring the left-most and then the top-most one on ties.
+fn master(ws: &Node) -> Option<&Node> {
+    ws.nodes.iter().flat_map(visible_leaves).min_by_key(|n| {
+        let area = n.rect.width as i64 * n.rect.height as i64;
+        (-area, n.rect.x, n.rect.y)
+    })
+}
+
+/// Follow the focus stacks of `node` to the most recently focused tiled leaf,
+/// other than the one with ID `exclude`.
+fn recent_leaf(node: &Node, exclude: i64) -> Option<&Node> {
+    if node.nodes.is_empty() {
+        return (node.id != exclude && node.node_type == NodeType::Con).then_some(node);
+    }
+    node.focus.iter().find_map(|id| {
+        let child = node.nodes.iter().find(|n| n.id == *id)?;
+        recent_leaf(child, exclude)
+    })
+}
+
+/// Collect the leaves of `node` in order of recency, following the focus stacks.
+fn recent_leaves(node: &Node) -> Vec<&Node> {
+    if node.nodes.is_empty() && node.floating_nodes.is_empty() {
+        return vec![node];
+    }
+    let children = node.nodes.iter().chain(&node.floating_nodes);
+    node.focus
+        .iter()
+        .filter_map(|id| children.clone().find(|n| n.id == *id))
+        .flat_map(recent_leaves)
+        .collect()
+}
+
+/// If `target` has `distinct_apps` set, keep only the first of the `windows` of each app,
+/// and none of the app of `focused`. Windows without a known app are all kept.
+fn distinct_apps<'a>(target: &Target, focused: &Node, windows: Vec<&'a Node>) -> Vec<&'a Node> {
+    if !target.distinct_apps {
+        return windows;
+    }
+    let mut seen: Vec<&str> = app(focused).into_iter().collect();
+    windows
+        .into_iter()
+        .filter(|n| match app(n) {
+            Some(app) if seen.contains(&app) => false,
+            Some(app) => {
+                seen.push(app);
+                true
+            }
+            None => true,
+        })
+        .collect()
+}
+
+/// Find a dialog of the window `focused` within the workspace `ws`,
+/// or the window that `focused` is a dialog of.
+/// Windows are related through `transient_for` where available,
+/// and otherwise through tiled and floating windows sharing a process.
+fn dialog_partner<'a>(ws: &'a Node, focused: &Node) -> Option<&'a Node> {
+    let tiled: Vec<&Node> = ws.nodes.iter().flat_map(leaves).collect();
+    let floating: Vec<&Node> = ws.floating_nodes.iter().flat_map(leaves).collect();
+    let mut windows = tiled.iter().chain(&floating).copied();
+    let transient_for = |n: &Node| n.window_properties.as_ref()?.transient_for.map(i64::from);
+
+    if let Some(parent) = transient_for(focused) {
+        return windows.find(|n| n.window == Some(parent));
+    }
+    if let Some(window) = focused.window {
+        if let Some(dialog) = windows.find(|n| transient_for(n) == Some(window)) {
+            return Some(dialog);
+        }
+    }
+    let focused_floats = floating.iter().any(|n| n.id == focused.id);
+    let others = if focused_floats { &tiled } else { &floating };
+    others
+        .iter()
+        .find(|n| focused.pid.is_some() && n.pid == focused.pid)
+        .copied()
+}
+
+/// Select the candidate closest to `origin`.
+/// Candidates are compared by distance from the center of `origin` to the closest point of the
+/// candidate, with ties broken by distance between centers.
+fn nearest<'a>(origin: &Rect, candidates: impl Iterator<Item = &'a Node>) -> Option<&'a Node> {
+    let c = center(origin);
+    candidates.min_by_key(|n| {
+        let dist = (
+            c.sq_dist(&closest_point(&n.rect, &c)),
+            c.sq_dist(&center(&n.rect)),
+        );
+        trace!("Distance to {}: {dist:?}", n.id);
+        dist
+    })
+}
+
+/// Adjacency of outputs, built from their geometry.
+/// An output is adjacent to another in a direction if its far edge in that direction
+/// touches the near edge of the other, and the two overlap along that edge.
+struct OutputGraph<'a> {
+    /// Adjacent outputs by output ID, then by whether the direction is backward and vertical.
+    edges: HashMap<(i64, bool, bool), Vec<&'a Node>>,
+}
+
+impl<'a> OutputGraph<'a> {
+    fn new(outputs: &[&'a Node]) -> Self {
+        let mut edges: HashMap<_, Vec<_>> = HashMap::new();
+        for (a, b) in outputs
+            .iter()
+            .flat_map(|a| outputs.iter().map(move |b| (a, b)))
+        {
+            for (backward, vertical) in [(false, false), (true, false), (false, true), (true, true)]
+            {
+                let (a_pos, a_dim) = component(&a.rect, vertical);
+                let (b_pos, b_dim) = component(&b.rect, vertical);
+                let touching =
+                    if backward { b_pos + b_dim == a_pos } else { a_pos + a_dim == b_pos };
+                if touching && overlap(&a.rect, &b.rect, vertical) > 0 {
+                    edges
+                        .entry((a.id, backward, vertical))
+                        .or_default()
+                        .push(*b);
+                }
+            }
+        }
+        trace!(
+            "Output graph: {:?}",
+            edges.iter().map(|(k, v)| (k, v.len()))
+        );
+        Self { edges }
+    }
+
+    /// The outputs adjacent to output `id` in the given direction.
+    fn adjacent(&self, id: i64, backward: bool, vertical: bool) -> &[&'a Node] {
+        self.edges
+            .get(&(id, backward, vertical))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The last output reached by moving from `from` in the given direction,
+    /// if it isn't `from` itself, fx. the left-most output of its row.
+    /// Each step continues to the adjacent output that overlaps `from` the most.
+    fn last(&self, from: &'a Node, backward: bool, vertical: bool) -> Option<&'a Node> {
+        let mut visited = vec![from.id];
+        let mut node = from;
+        while let Some(next) = self
+            .adjacent(node.id, backward, vertical)
+            .iter()
+            .filter(|n| !visited.contains(&n.id))
+            .max_by_key(|n| (overlap(&from.rect, &n.rect, vertical), -n.id))
+        {
+            visited.push(next.id);
+            node = next;
+        }
+        (node.id != from.id).then_some(node)
+    }
+}
+
+/// Selects the x or y component of a rect, depending on whether the direction is vertical.
+fn component(r: &Rect, vertical: bool) -> (i32, i32) {
+    if vertical {
+        (r.y, r.height)
+    } else {
+        (r.x, r.width)
+    }
+}
+
+/// Overlap of two rects along the axis perpendicular to the direction.
+fn overlap(a: &Rect, b: &Rect, vertical: bool) -> i32 {
+    let (a_pos, a_dim) = component(a, !vertical);
+    let (b_pos, b_dim) = component(b, !vertical);
+    (a_pos + a_dim).min(b_pos + b_dim) - a_pos.max(b_pos)
+}
+
+/// Find a leaf in a (presumed) neighboring container, respecting target edge-modes.
+/// Descent stops at nodes without children, so an empty workspace is itself a valid result.
+fn select_leaf<'a>(mut t: &'a Node, targets: &[Target], options: &Options) -> &'a Node {
     loop {
         debug!("Node {}", t.id);
         // Match the current node with targets
@@ -187,11 +833,18 @@ fn select_leaf<'a>(mut t: &'a Node, targets: &[Target]) -> &'a Node {
             // If the target has [EdgeMode::Traverse],
             // choose the closest neighbor to focused node.
             // Fx. if moving right, the left-most child is selected.
-            Some(target) if target.edge_mode == EdgeMode::Traverse => {
+            Some(target)
+                if target.edge_mode == EdgeMode::Traverse && target.kind != Kind::Empty =>
+            {
                 trace!("Matched traversing {:?}", target.kind);
-                // For floats, this requires comparing geometry
-                if target.kind == Kind::Float {
-                    trace!("Float container, selecting left/right/top/bottom-most child");
+                // For floats and windows, this requires comparing geometry
+                if target.kind == Kind::Float || target.kind == Kind::Window {
+                    trace!("Selecting left/right/top/bottom-most child");
+                    let children = if target.kind == Kind::Window {
+                        window_candidates(t, options.include_hidden)
+                    } else {
+                        t.floating_nodes.iter().collect()
+                    };
                     let key = |n: &&Node| {
                         let center = if target.vertical {
                             n.rect.y + n.rect.height / 2
@@ -201,9 +854,17 @@ fn select_leaf<'a>(mut t: &'a Node, targets: &[Target]) -> &'a Node {
                         (center, -n.id)
                     };
                     if target.backward {
-                        t.floating_nodes.iter().max_by_key(key)
+                        children.into_iter().max_by_key(key)
+                    } else {
+                        children.into_iter().min_by_key(key)
+                    }
+                } else if target.kind == Kind::Created {
+                    trace!("Selecting oldest or newest window");
+                    let windows = leaves(t).into_iter();
+                    if target.backward {
+                        windows.max_by_key(|n| n.id)
                     } else {
-                        t.floating_nodes.iter().min_by_key(key)
+                        windows.min_by_key(|n| n.id)
                     }
                 // NOTE: We don't handle outputs, as we will never move from one `Root` to another.
                 // For other container types, we can just select the first or last.
@@ -225,3 +886,202 @@ fn select_leaf<'a>(mut t: &'a Node, targets: &[Target]) -> &'a Node {
     debug!("Selected leaf {}", t.id);
     t
 }
+
+/// Choose the number of a new workspace to create for an [Kind::Empty] target.
+/// Moving forward selects the lowest unused number above the focused workspace,
+/// moving backward selects the highest unused number below it.
+pub fn new_workspace(workspaces: &[Workspace], target: &Target) -> Option<i32> {
+    let current = workspaces
+        .iter()
+        .find(|w| w.focused)
+        .map_or(0, |w| w.num.max(0));
+    let used = |n: i32| workspaces.iter().any(|w| w.num == n);
+    debug!("Searching for unused workspace number relative to {current}");
+    let res = if target.backward {
+        (1..current).rev().find(|n| !used(*n))
+    } else {
+        (current + 1..).find(|n| !used(*n))
+    };
+    if res.is_none() && target.edge_mode == EdgeMode::Wrap {
+        trace!("No unused number, wrapping around");
+        let last = workspaces.iter().map(|w| w.num).max().unwrap_or(0);
+        return Some(last.max(0) + 1);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// A node of `node_type` with `nodes` as children, focused in order.
+    fn node(id: i64, node_type: &str, [x, y, width, height]: [i32; 4], nodes: Vec<Value>) -> Value {
+        let rect = json!({"x": x, "y": y, "width": width, "height": height});
+        let focus: Vec<&Value> = nodes.iter().map(|n| &n["id"]).collect();
+        json!({
+            "id": id, "name": null, "type": node_type, "border": "none",
+            "current_border_width": 0, "layout": "splith", "percent": null,
+            "rect": rect, "window_rect": rect, "deco_rect": rect, "geometry": rect,
+            "urgent": false, "focused": false, "focus": focus,
+            "nodes": nodes, "floating_nodes": [], "sticky": false,
+        })
+    }
+
+    /// An output holding a workspace with a single window, with IDs following `id`.
+    fn output(id: i64, rect: [i32; 4]) -> Value {
+        let window = node(id + 2, "con", rect, vec![]);
+        node(
+            id,
+            "output",
+            rect,
+            vec![node(id + 1, "workspace", rect, vec![window])],
+        )
+    }
+
+    /// Three outputs in a row, with a fourth above the middle one.
+    /// The window on the output with ID `focused` is focused.
+    fn outputs(focused: i64) -> Node {
+        let mut outputs = vec![
+            output(10, [0, 0, 100, 100]),
+            output(20, [100, 0, 100, 100]),
+            output(30, [200, 0, 100, 100]),
+            output(40, [100, -100, 100, 100]),
+        ];
+        let idx = outputs.iter().position(|o| o["id"] == focused).unwrap();
+        outputs[idx]["nodes"][0]["nodes"][0]["focused"] = json!(true);
+        outputs.swap(0, idx);
+        serde_json::from_value(node(1, "root", [-100, -100, 400, 200], outputs)).unwrap()
+    }
+
+    fn output_target(backward: bool, vertical: bool, edge_mode: EdgeMode) -> [Target; 1] {
+        let target = Target::new(Kind::Output)
+            .backward(backward)
+            .vertical(vertical)
+            .edge_mode(edge_mode);
+        [target]
+    }
+
+    /// A workspace with a float at `rect`, and another float to the right of it.
+    fn floats(rect: [i32; 4]) -> Node {
+        let mut ws = node(1, "workspace", [0, 0, 1000, 1000], vec![]);
+        let float = node(10, "floating_con", rect, vec![]);
+        let beside = node(11, "floating_con", [500, 150, 100, 100], vec![]);
+        ws["floating_nodes"] = json!([float, beside]);
+        serde_json::from_value(ws).unwrap()
+    }
+
+    #[test]
+    fn snap_position_stops_at_float_beside() {
+        let ws = floats([100, 100, 200, 200]);
+        let float = &ws.floating_nodes[0];
+        assert_eq!(snap_position(&ws, float, false, false), Some(300));
+        assert_eq!(snap_position(&ws, float, true, false), Some(0));
+    }
+
+    #[test]
+    fn snap_position_ignores_floats_not_beside() {
+        let ws = floats([100, 100, 200, 200]);
+        let float = &ws.floating_nodes[0];
+        assert_eq!(snap_position(&ws, float, false, true), Some(800));
+        assert_eq!(snap_position(&ws, float, true, true), Some(0));
+    }
+
+    #[test]
+    fn snap_position_at_edge_is_none() {
+        let ws = floats([0, 100, 200, 200]);
+        assert_eq!(snap_position(&ws, &ws.floating_nodes[0], true, false), None);
+    }
+
+    /// Workspaces numbered `nums`, where the one numbered `focused` is focused.
+    fn workspaces(nums: &[i32], focused: i32) -> Vec<Workspace> {
+        let workspace = |num: i32| {
+            serde_json::from_value(json!({
+                "id": num, "num": num, "name": num.to_string(), "visible": num == focused,
+                "focused": num == focused, "urgent": false, "representation": null,
+                "rect": {"x": 0, "y": 0, "width": 1920, "height": 1080},
+                "output": "DP-1", "focus": [],
+            }))
+            .unwrap()
+        };
+        nums.iter().copied().map(workspace).collect()
+    }
+
+    #[test]
+    fn new_workspace_finds_unused_number() {
+        let forward = Target::new(Kind::Empty);
+        let backward = forward.backward(true);
+        assert_eq!(new_workspace(&workspaces(&[1, 2, 4], 1), &forward), Some(3));
+        assert_eq!(
+            new_workspace(&workspaces(&[1, 2, 4], 4), &backward),
+            Some(3)
+        );
+        assert_eq!(new_workspace(&workspaces(&[2, 3], 3), &backward), Some(1));
+    }
+
+    #[test]
+    fn new_workspace_wraps_past_last_number() {
+        let backward = Target::new(Kind::Empty).backward(true);
+        assert_eq!(new_workspace(&workspaces(&[1, 2, 4], 1), &backward), None);
+        let wrap = backward.edge_mode(EdgeMode::Wrap);
+        assert_eq!(new_workspace(&workspaces(&[1, 2, 4], 1), &wrap), Some(5));
+    }
+
+    #[test]
+    fn search_reports_ancestor_containing_neighbor() {
+        // The focused window is last in its split, so traversing finds the neighbor further out
+        let rect = [0, 0, 100, 100];
+        let mut inner = node(
+            4,
+            "con",
+            rect,
+            vec![node(5, "con", rect, vec![]), node(6, "con", rect, vec![])],
+        );
+        inner["nodes"][1]["focused"] = json!(true);
+        inner["focus"] = json!([6, 5]);
+        let workspace = node(
+            3,
+            "workspace",
+            rect,
+            vec![inner, node(7, "con", rect, vec![])],
+        );
+        let output = node(2, "output", rect, vec![workspace]);
+        let tree: Node = serde_json::from_value(node(1, "root", rect, vec![output])).unwrap();
+
+        let target = Target::new(Kind::Split).edge_mode(EdgeMode::Traverse);
+        let search = search(&tree, &[target], &Options::default());
+        assert_eq!(search.neighbor.map(|n| n.id), Some(7));
+        assert_eq!(search.ancestor.map(|n| n.id), Some(3));
+        assert_eq!(matching_ancestor(&tree, &[target]).map(|n| n.id), Some(4));
+    }
+
+    #[test]
+    fn output_moves_to_adjacent_output() {
+        let options = Options::default();
+        let right = output_target(false, false, EdgeMode::Stop);
+        let found = neighbor(&outputs(10), &right, &options).map(|n| n.id);
+        assert_eq!(found, Some(22));
+        let up = output_target(true, true, EdgeMode::Stop);
+        let found = neighbor(&outputs(20), &up, &options).map(|n| n.id);
+        assert_eq!(found, Some(42));
+    }
+
+    #[test]
+    fn output_wraps_within_row() {
+        let options = Options::default();
+        let right = output_target(false, false, EdgeMode::Wrap);
+        let found = neighbor(&outputs(30), &right, &options).map(|n| n.id);
+        assert_eq!(found, Some(12));
+        let left = output_target(true, false, EdgeMode::Wrap);
+        let found = neighbor(&outputs(10), &left, &options).map(|n| n.id);
+        assert_eq!(found, Some(32));
+    }
+
+    #[test]
+    fn output_wraps_within_column() {
+        let options = Options::default();
+        let up = output_target(true, true, EdgeMode::Wrap);
+        let found = neighbor(&outputs(40), &up, &options).map(|n| n.id);
+        assert_eq!(found, Some(22));
+    }
+}
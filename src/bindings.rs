@@ -0,0 +1,53 @@
+//! Generation of sway configuration snippets.
+use std::fmt::Write;
+
+/// Key names for left, down, up and right, in that order.
+pub type Keys = [String; 4];
+
+/// Resolve a key layout, either a preset name or four comma-separated key names.
+pub fn parse_keys(layout: &str) -> Option<Keys> {
+    let keys: Vec<&str> = match layout {
+        "vim" => vec!["h", "j", "k", "l"],
+        "arrows" => vec!["Left", "Down", "Up", "Right"],
+        "wasd" => vec!["a", "s", "w", "d"],
+        _ => layout.split(',').collect(),
+    };
+    let keys: Vec<String> = keys.into_iter().map(str::to_string).collect();
+    keys.try_into().ok()
+}
+
+/// Default targets for navigating in the direction with the character `c`.
+pub fn default_targets(c: char) -> [String; 3] {
+    [
+        format!("split-{c}t"),
+        format!("float-{c}t"),
+        format!("output-{c}s"),
+    ]
+}
+
+/// Generate a binding mode block named `mode` using the given `keys`.
+pub fn binding_mode(mode: &str, keys: &Keys) -> String {
+    let directions = [
+        ("left", 'l', "shrink width"),
+        ("down", 'd', "grow height"),
+        ("up", 'u', "shrink height"),
+        ("right", 'r', "grow width"),
+    ];
+    let mut block = format!("mode \"{mode}\" {{\n");
+    for (key, (_, c, _)) in keys.iter().zip(directions) {
+        let targets = default_targets(c).join(" ");
+        writeln!(block, "    bindsym {key} exec sway-overfocus {targets}").unwrap();
+    }
+    for (key, (dir, _, _)) in keys.iter().zip(directions) {
+        writeln!(block, "    bindsym Shift+{key} move {dir}").unwrap();
+    }
+    for (key, (_, _, resize)) in keys.iter().zip(directions) {
+        writeln!(block, "    bindsym Ctrl+{key} resize {resize} 10px").unwrap();
+    }
+    block.push_str("    bindsym Tab exec sway-overfocus group-rw group-dw\n");
+    block.push_str("    bindsym Shift+Tab exec sway-overfocus group-lw group-uw\n");
+    block.push_str("    bindsym Return mode \"default\"\n");
+    block.push_str("    bindsym Escape mode \"default\"\n");
+    block.push('}');
+    block
+}
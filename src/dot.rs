@@ -0,0 +1,52 @@
+//! Graphviz rendering of the tree.
+use crate::tree::{describe, focus_local};
+use std::fmt::Write;
+use swayipc_types::{Node, NodeLayout};
+
+/// Render `tree` as a DOT graph.
+/// Nodes on the focus path are drawn in bold, and `neighbor` is filled, if given.
+pub fn render(tree: &Node, neighbor: Option<i64>) -> String {
+    let mut focus_path = Vec::new();
+    let mut t = tree;
+    focus_path.push(t.id);
+    while let Some(child) = focus_local(t) {
+        focus_path.push(child.id);
+        t = child;
+    }
+
+    let mut dot = "digraph tree {\n    node [shape=box];\n".to_string();
+    render_node(&mut dot, tree, &focus_path, neighbor);
+    dot.push('}');
+    dot
+}
+
+/// Append the node and edge statements for `node` and its descendants.
+fn render_node(dot: &mut String, node: &Node, focus_path: &[i64], neighbor: Option<i64>) {
+    let mut label = describe(node);
+    if node.layout != NodeLayout::None {
+        write!(label, "\n{:?}", node.layout).unwrap();
+    }
+    let mut attrs = format!("label=\"{}\"", escape(&label));
+    match (focus_path.contains(&node.id), neighbor == Some(node.id)) {
+        (true, _) => attrs.push_str(", style=bold"),
+        (false, true) => attrs.push_str(", style=filled, fillcolor=lightblue"),
+        (false, false) => (),
+    }
+    writeln!(dot, "    n{} [{attrs}];", node.id).unwrap();
+
+    for child in &node.nodes {
+        writeln!(dot, "    n{} -> n{};", node.id, child.id).unwrap();
+        render_node(dot, child, focus_path, neighbor);
+    }
+    for child in &node.floating_nodes {
+        writeln!(dot, "    n{} -> n{} [style=dashed];", node.id, child.id).unwrap();
+        render_node(dot, child, focus_path, neighbor);
+    }
+}
+
+/// Escape `s` for use in a quoted DOT string.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
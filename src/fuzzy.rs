@@ -0,0 +1,57 @@
+//! Fuzzy matching of window titles and app IDs.
+
+/// Score how well `query` matches `text`, ignoring case.
+/// All characters of the query must appear in order, higher scores are better.
+/// Consecutive characters and characters at the start of words score higher.
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut prev_matched = false;
+    let mut prev_char = None;
+    for c in text.chars().flat_map(char::to_lowercase) {
+        let Some(q) = query.peek() else {
+            break;
+        };
+        if *q == c {
+            query.next();
+            score += 1;
+            if prev_matched {
+                score += 2;
+            }
+            if !prev_char.is_some_and(char::is_alphanumeric) {
+                score += 3;
+            }
+            prev_matched = true;
+        } else {
+            prev_matched = false;
+        }
+        prev_char = Some(c);
+    }
+    query.peek().is_none().then_some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_requires_characters_in_order() {
+        assert!(score("ffx", "firefox").is_some());
+        assert!(score("FFX", "Firefox").is_some());
+        assert_eq!(score("xf", "firefox"), None);
+        assert_eq!(score("firefoxes", "firefox"), None);
+        assert_eq!(score("", "firefox"), Some(0));
+    }
+
+    #[test]
+    fn score_prefers_consecutive_characters() {
+        assert_eq!(score("fi", "firefox"), Some(7));
+        assert_eq!(score("fr", "firefox"), Some(5));
+    }
+
+    #[test]
+    fn score_prefers_word_starts() {
+        assert_eq!(score("ff", "fire fox"), Some(8));
+        assert_eq!(score("ff", "firefox"), Some(5));
+    }
+}
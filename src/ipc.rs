@@ -0,0 +1,55 @@
+//! Raw IPC access to sway.
+use std::io::{Read, Write};
+use std::mem;
+use std::os::unix::net::UnixStream;
+use sway_overfocus::partial::parse_tree_partial;
+use swayipc::{CommandType, Connection, Error, Fallible, Node, MAGIC};
+
+/// Send `command` over the socket of `c` and return the unparsed reply.
+pub fn request(c: &mut Connection, command: CommandType) -> Fallible<Vec<u8>> {
+    // The connection only hands out its socket by value, so a placeholder takes its place
+    let (placeholder, _) = UnixStream::pair()?;
+    let mut stream = UnixStream::from(mem::replace(c, placeholder.into()));
+    let res = request_on(&mut stream, command);
+    *c = stream.into();
+    res
+}
+
+/// Send `command` over `stream` and return the unparsed reply.
+fn request_on(stream: &mut UnixStream, command: CommandType) -> Fallible<Vec<u8>> {
+    stream.write_all(&command.encode())?;
+
+    let mut header = [0_u8; 14];
+    stream.read_exact(&mut header)?;
+    let magic: [u8; 6] = header[0..6].try_into().unwrap();
+    if magic != MAGIC {
+        return Err(Error::InvalidMagic(magic));
+    }
+    let len = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let reply_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    if reply_type != u32::from(command) {
+        return Err(Error::InvalidCommandType(reply_type, command.into()));
+    }
+    let mut payload = vec![0_u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+/// Retrieve the tree, timing its retrieval and deserialization separately.
+pub fn get_tree(c: &mut Connection) -> Fallible<Node> {
+    let payload = request(c, CommandType::GetTree)?;
+    crate::profile::lap("receive tree");
+    let tree = serde_json::from_slice(&payload)?;
+    crate::profile::lap("deserialize tree");
+    Ok(tree)
+}
+
+/// Retrieve the tree, only parsing the subtree of the workspace with ID `workspace`.
+/// Other workspaces are included, but without any children.
+pub fn get_tree_partial(c: &mut Connection, workspace: i64) -> Fallible<Node> {
+    let payload = request(c, CommandType::GetTree)?;
+    crate::profile::lap("receive tree");
+    let tree = parse_tree_partial(&payload, workspace)?;
+    crate::profile::lap("deserialize tree");
+    Ok(tree)
+}
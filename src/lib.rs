@@ -0,0 +1,9 @@
+//! Tree handling and neighbor searching, without connecting to sway.
+//! The library builds without the `ipc` feature,
+//! fx. for WebAssembly with `--lib --no-default-features`.
+pub mod algorithm;
+pub mod dot;
+pub mod parse;
+pub mod partial;
+pub mod pretty;
+pub mod tree;
@@ -0,0 +1,86 @@
+//! Structured logging, one JSON object per line.
+use log::kv::{self, Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::{Map, Value as Json};
+use std::io::Write;
+use std::sync::Once;
+use std::time::SystemTime;
+
+/// Format of log lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable lines, only logged in debug builds.
+    #[default]
+    Text,
+    /// One JSON object per line, with the key-value fields of each record.
+    Json,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Logger writing each record to stderr as a JSON object.
+struct JsonLogger;
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let mut event = Map::new();
+        event.insert("time".into(), time.into());
+        event.insert("level".into(), record.level().as_str().into());
+        event.insert("module".into(), record.target().into());
+        event.insert("message".into(), record.args().to_string().into());
+        let _ = record.key_values().visit(&mut Fields(&mut event));
+        let _ = writeln!(std::io::stderr().lock(), "{}", Json::Object(event));
+    }
+
+    fn flush(&self) {}
+}
+
+/// Adds the key-value fields of a record to its JSON object.
+struct Fields<'a>(&'a mut Map<String, Json>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = serde_json::to_value(value).map_err(kv::Error::boxed)?;
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+static LOGGER: JsonLogger = JsonLogger;
+static INIT: Once = Once::new();
+
+/// Install the logger for `format`, at the level given by `RUST_LOG` or `info` by default.
+/// Only the first call has an effect, so every set of parsed flags can call it.
+pub fn init(format: Format) {
+    INIT.call_once(|| match format {
+        Format::Json => {
+            if log::set_logger(&LOGGER).is_ok() {
+                let level = std::env::var("RUST_LOG").ok().and_then(|l| l.parse().ok());
+                log::set_max_level(level.unwrap_or(LevelFilter::Info));
+            }
+        }
+        #[cfg(profile = "debug")]
+        Format::Text => env_logger::init(),
+        #[cfg(not(profile = "debug"))]
+        Format::Text => (),
+    });
+}
@@ -1,96 +1,1552 @@
-use log::info;
-use swayipc::Connection;
+use log::{info, warn};
+use std::fs::File;
+use std::time::{Duration, Instant, SystemTime};
+use swayipc::{Connection, Event, EventType, NodeType, WindowChange};
 
-mod algorithm;
-use algorithm::{EdgeMode, Kind, Target};
-mod tree;
+use sway_overfocus::{algorithm, dot, parse, pretty, tree};
+
+mod bindings;
+mod fuzzy;
+mod ipc;
+mod logging;
+mod output;
+use algorithm::{Kind, Options, Target};
+use parse::ParseError;
+mod profile;
+mod record;
 
 #[derive(Debug)]
 enum FocusError {
     Args,
+    Parse(ParseError),
+    Flag(String),
+    FlagValue(String),
     Command,
+    NoNeighbor,
+    Replay(usize),
+    Doctor(usize),
+    /// A command, the message of sway, and the index of the rejected part of the command.
+    Rejected(String, String, usize),
+    Io(std::io::Error),
     SwayIPC(swayipc::Error),
 }
 
 fn main() {
-    #[cfg(profile = "debug")]
-    env_logger::init();
-
-    match task() {
-        Err(e) => {
-            match e {
-                FocusError::Args => eprint!("{}", include_str!("../usage.md")),
-                FocusError::Command => eprintln!("error: no valid focus command"),
-                FocusError::SwayIPC(e) => eprintln!("swayipc error: {e}"),
-            };
-            std::process::exit(1);
-        }
-        Ok(()) => (),
+    if let Err(e) = task() {
+        match e {
+            FocusError::Args => eprint!("{}", include_str!("../usage.md")),
+            FocusError::Parse(e) => eprintln!("{e}"),
+            FocusError::Flag(flag) => eprintln!("error: unknown flag '{flag}'"),
+            FocusError::FlagValue(flag) => {
+                eprintln!("error: missing or invalid value for '{flag}'")
+            }
+            FocusError::Command => eprintln!("error: no valid focus command"),
+            FocusError::NoNeighbor => (),
+            FocusError::Replay(n) => eprintln!("error: {n} replayed cases differ"),
+            FocusError::Doctor(n) => eprintln!("error: {n} checks failed"),
+            FocusError::Rejected(cmd, msg, _) => eprintln!("error: sway rejected '{cmd}': {msg}"),
+            FocusError::Io(e) => eprintln!("io error: {e}"),
+            FocusError::SwayIPC(e) => eprintln!("swayipc error: {e}"),
+        };
+        std::process::exit(1);
     }
 }
 
 fn task() -> Result<(), FocusError> {
-    info!("Parsing arguments");
     let args: Box<[String]> = std::env::args().collect();
-    let targets = parse_args(&args).ok_or(FocusError::Args)?;
+    // When called through a link named fx. `overfocus-left`, navigate in that direction
+    let program = args.first().map(std::path::Path::new);
+    let program = program.and_then(|p| p.file_name()?.to_str());
+    if let Some(direction) = program.and_then(|p| p.strip_prefix("overfocus-")) {
+        let (name, _, _) = DIRECTIONS
+            .iter()
+            .find(|(name, _, _)| *name == direction)
+            .ok_or(FocusError::Args)?;
+        let c = name.chars().next().unwrap_or_default();
+        let mut line = args[1..].to_vec();
+        line.extend(bindings::default_targets(c));
+        return focus(&line);
+    }
+    // Other subcommands install the logger once their flags are parsed
+    let subcommand = args.get(1).map(String::as_str);
+    if let Some("binding-mode" | "replay" | "minimize" | "doctor" | "focus-urgent" | "raise") =
+        subcommand
+    {
+        logging::init(logging::Format::Text);
+    }
+    match subcommand {
+        Some("print-candidates") => print_candidates(&args[2..]),
+        Some("scratchpad") => scratchpad(&args[2..]),
+        Some("binding-mode") => binding_mode(&args[2..]),
+        Some("waybar") => waybar(&args[2..]),
+        Some("can-focus") => can_focus(&args[2..]),
+        Some("dump-tree") => dump_tree(&args[2..]),
+        Some("replay") => replay(&args[2..]),
+        Some("minimize") => minimize(&args[2..]),
+        Some("goto") => goto(&args[2..]),
+        Some("swap-outputs") => swap_outputs(&args[2..]),
+        Some("balance") => balance(&args[2..]),
+        Some("toggle-layout") => toggle_layout(&args[2..]),
+        Some("query") => query(&args[2..]),
+        Some("list") => list(&args[2..]),
+        Some("doctor") => doctor(),
+        Some("snap") => snap(&args[2..]),
+        Some("break-out") => break_out(&args[2..]),
+        Some("focus-urgent") => focus_urgent(&args[2..]),
+        Some("raise") => raise(&args[2..]),
+        Some("find") => find(&args[2..]),
+        Some("focus-at") => focus_at(&args[2..]),
+        Some("parent") => focus_level(&args[2..], true),
+        Some("child") => focus_level(&args[2..], false),
+        Some("-") => focus_stdin(),
+        _ => focus(&args[1..]),
+    }
+}
+
+/// Parts of the tree needed for resolving a set of targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    /// The entire tree.
+    Full,
+    /// The subtree of the focused workspace, other workspaces are left empty.
+    FocusedWorkspace,
+}
+
+impl Scope {
+    /// The smallest scope needed to resolve `targets`.
+    fn of(targets: &[Target]) -> Self {
+        let kinds = |f: fn(Kind) -> bool| targets.iter().all(|t| f(t.kind));
+        if kinds(|k| {
+            matches!(
+                k,
+                Kind::Split
+                    | Kind::Group
+                    | Kind::Float
+                    | Kind::Layer
+                    | Kind::Window
+                    | Kind::Dialog
+                    | Kind::Master
+                    | Kind::Stack
+                    | Kind::Recent
+                    | Kind::Created
+            )
+        }) {
+            Scope::FocusedWorkspace
+        } else {
+            Scope::Full
+        }
+    }
+}
+
+/// Run a command, failing if sway rejects any part of it.
+fn run_command(c: &mut Connection, cmd: &str) -> Result<(), FocusError> {
+    let outcomes = c.run_command(cmd).map_err(FocusError::SwayIPC)?;
+    let failed = outcomes
+        .into_iter()
+        .enumerate()
+        .find_map(|(i, res)| Some((i, res.err()?)));
+    match failed {
+        Some((i, swayipc::Error::CommandFailed(msg))) => {
+            Err(FocusError::Rejected(cmd.to_string(), msg, i))
+        }
+        Some((_, e)) => Err(FocusError::SwayIPC(e)),
+        None => Ok(()),
+    }
+}
+
+/// Retrieve and pre-process the parts of the tree within `scope`.
+/// Containers marked with the skip mark of `flags` are removed.
+fn get_tree(c: &mut Connection, scope: Scope, flags: &Flags) -> Result<swayipc::Node, FocusError> {
+    // Floats on other outputs are left out of the partial tree
+    let scope = match scope {
+        Scope::FocusedWorkspace if flags.options.visible_floats => Scope::Full,
+        scope => scope,
+    };
+    let tree = match scope {
+        Scope::FocusedWorkspace => {
+            info!("Retrieving focused workspace");
+            let workspaces = c.get_workspaces().map_err(FocusError::SwayIPC)?;
+            let focused = workspaces.iter().find(|w| w.focused).map(|w| w.id);
+            info!("Retrieving partial tree");
+            profile::lap("get workspaces");
+            match focused {
+                Some(id) => ipc::get_tree_partial(c, id),
+                None => ipc::get_tree(c),
+            }
+        }
+        Scope::Full => {
+            info!("Retrieving tree");
+            ipc::get_tree(c)
+        }
+    };
+    let tree = tree.map_err(FocusError::SwayIPC)?;
+    info!("Pre-processing tree");
+    let mut tree = tree::preprocess(tree);
+    if flags.skip_off_outputs {
+        let outputs = c.get_outputs().map_err(FocusError::SwayIPC)?;
+        tree::remove_outputs(&mut tree, &off_outputs(&outputs, flags));
+    }
+    tree::remove_marked(&mut tree, flags.skip_mark());
+    if !flags.no_collapse {
+        tree::collapse_chains(&mut tree);
+    }
+    profile::lap("pre-process");
+    Ok(tree)
+}
+
+/// Names of the outputs that are turned off, if they should be skipped.
+fn off_outputs(outputs: &[swayipc::Output], flags: &Flags) -> Vec<String> {
+    let off = outputs.iter().filter(|o| flags.skip_off_outputs && !o.dpms);
+    off.map(|o| o.name.clone()).collect()
+}
+
+/// Mark used for previewing a neighbor.
+const PREVIEW_MARK: &str = "_overfocus_preview";
+
+/// Default mark for containers that are ignored by navigation.
+const SKIP_MARK: &str = "_overfocus_skip";
+
+/// Flags given before the targets.
+#[derive(Debug, Default)]
+struct Flags {
+    /// Mark the neighbor instead of focusing it.
+    preview: bool,
+    /// Search from the previewed container, if any, when previewing.
+    cycle: bool,
+    /// Focus the previously marked neighbor.
+    commit: bool,
+    /// Keep printing updates.
+    follow: bool,
+    /// Move the focused container to the neighbor instead of focusing it.
+    moving: bool,
+    /// Stay on the workspace of the moved container.
+    no_follow: bool,
+    /// Options for the generated focus command.
+    command: tree::CommandOptions,
+    /// Print how long each stage of the navigation took.
+    profile: bool,
+    /// Skip navigating if another navigation ran within this many milliseconds.
+    debounce: Option<u64>,
+    /// Mark of containers to ignore, if not the default.
+    skip_mark: Option<String>,
+    /// Run sway's own focus command if no neighbor is found.
+    fallback_native: bool,
+    /// Ignore outputs that are turned off.
+    skip_off_outputs: bool,
+    /// Keep split containers with a single child.
+    no_collapse: bool,
+    /// Name of workspaces created by `empty` targets, with `{n}` replaced by the number.
+    workspace_name: Option<String>,
+    /// Shell command to run with the title of the newly focused window.
+    announce: Option<String>,
+    /// Format of printed results, if not the default of the subcommand.
+    output_format: Option<output::Format>,
+    /// Format of log lines.
+    log_format: logging::Format,
+    /// Send a tick event describing each navigation.
+    tick: bool,
+    /// Directory to record navigations to.
+    record: Option<std::path::PathBuf>,
+    /// Options for the search.
+    options: Options,
+}
+
+impl Flags {
+    /// Mark of containers to ignore.
+    fn skip_mark(&self) -> &str {
+        self.skip_mark.as_deref().unwrap_or(SKIP_MARK)
+    }
+}
+
+/// Split leading flags from the remaining arguments.
+fn parse_flags(args: &[String]) -> Result<(Flags, &[String]), FocusError> {
+    let mut flags = Flags::default();
+    let mut rest = args;
+    while let Some((arg, mut tail)) = rest.split_first() {
+        let invalid = || FocusError::FlagValue(arg.clone());
+        // Takes the value following a flag
+        let mut value = || -> Result<&String, FocusError> {
+            let (value, value_tail) = tail.split_first().ok_or_else(invalid)?;
+            tail = value_tail;
+            Ok(value)
+        };
+        match arg.as_str() {
+            "--preview" => flags.preview = true,
+            "--cycle" => (flags.preview, flags.cycle) = (true, true),
+            "--commit" => flags.commit = true,
+            "--follow" => flags.follow = true,
+            "--move" => flags.moving = true,
+            "--no-follow" => flags.no_follow = true,
+            "--parent" => flags.options.from_parent = true,
+            "--numbered" => flags.command.numbered = true,
+            "--no-auto-back-and-forth" => flags.command.no_auto_back_and_forth = true,
+            "--strict-float" => flags.options.strict_float = true,
+            "--visible-floats" => flags.options.visible_floats = true,
+            "--include-hidden" => flags.options.include_hidden = true,
+            "--outermost" => flags.options.outermost = true,
+            "--no-collapse" => flags.no_collapse = true,
+            "--skip-off-outputs" => flags.skip_off_outputs = true,
+            "--fallback-native" => flags.fallback_native = true,
+            "--profile" => flags.profile = true,
+            "--tick" => flags.tick = true,
+            "--skip-mark" => flags.skip_mark = Some(value()?.clone()),
+            "--announce" => flags.announce = Some(value()?.clone()),
+            "--workspace-name" => {
+                let pattern = value()?;
+                if !pattern.contains("{n}") {
+                    return Err(invalid());
+                }
+                flags.workspace_name = Some(pattern.clone());
+            }
+            "--record" => flags.record = Some(value()?.into()),
+            "--output-format" => {
+                flags.output_format = Some(output::Format::from_name(value()?).ok_or_else(invalid)?)
+            }
+            "--log-format" => {
+                flags.log_format = logging::Format::from_name(value()?).ok_or_else(invalid)?
+            }
+            "--debounce" => flags.debounce = Some(value()?.parse().map_err(|_| invalid())?),
+            // Starts a long-form target
+            "--kind" => break,
+            _ if arg.starts_with("--") => return Err(FocusError::Flag(arg.clone())),
+            _ => break,
+        }
+        rest = tail;
+    }
+    logging::init(flags.log_format);
+    Ok((flags, rest))
+}
+
+/// Focus the neighbor matching `args`.
+fn focus(args: &[String]) -> Result<(), FocusError> {
+    profile::start();
+    let line = args;
+    let (flags, args) = parse_flags(args)?;
+    let targets = if flags.commit { Box::default() } else { parse_targets(args)? };
+    profile::lap("parse arguments");
+
+    // Held until the navigation is done
+    let Some(_lock) = debounce(&flags) else {
+        return Ok(());
+    };
 
     info!("Starting connection");
     let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
-    info!("Retrieving tree");
-    let tree = c.get_tree().map_err(FocusError::SwayIPC)?;
-    info!("Pre-processing tree");
-    let tree = tree::preprocess(tree);
+    profile::lap("connect");
+    focus_with(&mut c, line, &flags, &targets)?;
+    if flags.profile {
+        profile::report();
+    }
+    Ok(())
+}
 
-    info!("Searching for neighbor");
-    let neighbor = algorithm::neighbor(&tree, &targets);
-    if let Some(neighbor) = neighbor {
-        let focus_cmd = tree::focus_command(neighbor).ok_or(FocusError::Command)?;
+/// Take the debounce lock if `flags` ask for it, returning `None` if the navigation
+/// should be skipped. The lock is held until the returned guard is dropped.
+fn debounce(flags: &Flags) -> Option<Option<DebounceLock>> {
+    match flags.debounce.map(debounce_lock) {
+        Some(Ok(None)) => {
+            info!("Debouncing, skipping navigation");
+            None
+        }
+        Some(Ok(lock)) => Some(lock),
+        Some(Err(e)) => {
+            warn!("Failed to take debounce lock: {e}");
+            Some(None)
+        }
+        None => Some(None),
+    }
+}
+
+/// A runtime lock file, removed when dropped.
+struct DebounceLock(std::path::PathBuf);
+
+impl Drop for DebounceLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Take the runtime lock file, unless another instance holds it
+/// or the last navigation happened less than `ms` milliseconds ago.
+/// The time of the last navigation is kept next to the lock file,
+/// in milliseconds since the epoch.
+fn debounce_lock(ms: u64) -> std::io::Result<Option<DebounceLock>> {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, Into::into);
+    let path = dir.join("sway-overfocus.lock");
+    match File::options().write(true).create_new(true).open(&path) {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            // A lock left behind by an instance that didn't exit cleanly is taken over
+            let age = std::fs::metadata(&path)?.modified()?.elapsed();
+            if age.unwrap_or_default() < Duration::from_secs(1) {
+                return Ok(None);
+            }
+            warn!("Taking over stale debounce lock");
+        }
+        Err(e) => return Err(e),
+    }
+    let lock = DebounceLock(path);
+
+    let last_path = dir.join("sway-overfocus.last");
+    let last = std::fs::read_to_string(&last_path).unwrap_or_default();
+    let last: u128 = last.trim().parse().unwrap_or(0);
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    if now.saturating_sub(last) < u128::from(ms) {
+        return Ok(None);
+    }
+    std::fs::write(last_path, now.to_string())?;
+    Ok(Some(lock))
+}
+
+/// Focus neighbors for each line of standard input, where each line holds flags and targets.
+/// All lines are executed in order over a single connection.
+/// Debouncing and profiling apply to each line on its own.
+fn focus_stdin() -> Result<(), FocusError> {
+    let mut connection = None;
+    for line in std::io::stdin().lines() {
+        let line = line.map_err(FocusError::Io)?;
+        let args: Box<[String]> = line.split_whitespace().map(str::to_string).collect();
+        if args.is_empty() {
+            continue;
+        }
+        profile::start();
+        let (flags, targets) = parse_flags(&args)?;
+        info!("Parsed line '{line}'");
+        let targets = if flags.commit { Box::default() } else { parse_targets(targets)? };
+        profile::lap("parse arguments");
+        // Held until the navigation of this line is done
+        let Some(_lock) = debounce(&flags) else {
+            continue;
+        };
+        // Connecting after parsing the first line, as the logger is installed by then
+        let c = match &mut connection {
+            Some(c) => c,
+            None => {
+                info!("Starting connection");
+                let c = connection.insert(Connection::new().map_err(FocusError::SwayIPC)?);
+                profile::lap("connect");
+                c
+            }
+        };
+        focus_with(c, &args, &flags, &targets)?;
+        if flags.profile {
+            profile::report();
+        }
+    }
+    Ok(())
+}
+
+/// Focus the neighbor matching `targets` using an existing connection.
+/// The targets are ignored when committing a preview.
+/// `args` are the arguments that `flags` and `targets` were parsed from, used for recording.
+fn focus_with(
+    c: &mut Connection,
+    args: &[String],
+    flags: &Flags,
+    targets: &[Target],
+) -> Result<(), FocusError> {
+    if flags.commit {
+        let cmd = format!("[con_mark=\"{PREVIEW_MARK}\"] focus; unmark {PREVIEW_MARK}");
+        info!("Running focus command: '{cmd}'");
+        run_command(c, &cmd)?;
+        return Ok(());
+    }
+    let scope = Scope::of(targets);
+    let mut tree = get_tree(c, scope, flags)?;
+    if flags.cycle {
+        let previewed = tree.find_as_ref(|n| n.marks.iter().any(|m| m == PREVIEW_MARK));
+        if let Some(id) = previewed.map(|n| n.id) {
+            info!("Searching from previewed container {id}");
+            tree::refocus(&mut tree, id);
+        }
+    }
+
+    if flags.moving {
+        info!("Searching for neighbor to move to");
+        let moved =
+            tree::focused_container(&tree, flags.options.from_parent).ok_or(FocusError::Command)?;
+        if let Some(neighbor) = algorithm::neighbor(&tree, targets, &flags.options) {
+            // Targets of a move share a direction, so the first one decides the placement
+            let backward = targets.first().is_some_and(|t| t.backward);
+            let mut cmd =
+                tree::move_command(moved, neighbor, backward).ok_or(FocusError::Command)?;
+            let path = tree::focus_path(&tree);
+            let workspace = path.iter().find(|n| n.node_type == NodeType::Workspace);
+            if let (true, Some(name)) = (flags.no_follow, workspace.and_then(|w| w.name.as_ref())) {
+                // Returning to the workspace must not be redirected back and forth
+                let options = tree::CommandOptions {
+                    no_auto_back_and_forth: true,
+                    ..flags.command
+                };
+                let workspace_cmd = tree::workspace_command(name, false, &options);
+                cmd = format!("{cmd}; {workspace_cmd}");
+            }
+            info!("Running move command: '{cmd}'");
+            run_command(c, &cmd)?;
+        }
+    } else if flags.preview {
+        info!("Searching for neighbor to preview");
+        let mut cmd = format!("unmark {PREVIEW_MARK}");
+        let neighbor = algorithm::neighbor(&tree, targets, &flags.options);
+        // Only containers can be marked, so workspaces and outputs mark their focused window
+        let marked = neighbor
+            .map(tree::focused_leaf)
+            .filter(|n| matches!(n.node_type, NodeType::Con | NodeType::FloatingCon));
+        match marked {
+            Some(marked) => {
+                cmd = format!("{cmd}; [con_id={}] mark --add {PREVIEW_MARK}", marked.id)
+            }
+            None if neighbor.is_some() => info!("Neighbor has no window to mark"),
+            None => (),
+        }
+        info!("Running preview command: '{cmd}'");
+        run_command(c, &cmd)?;
+    } else {
+        match focus_neighbor(c, &tree, args, flags, targets, true) {
+            // The neighbor may have closed since the tree was retrieved, so search once more.
+            // Only when nothing ran, as focus has already moved if a later part was rejected.
+            Err(FocusError::Rejected(cmd, msg, 0)) => {
+                info!("Command '{cmd}' failed with '{msg}', retrying");
+                let tree = get_tree(c, scope, flags)?;
+                focus_neighbor(c, &tree, args, flags, targets, false)?;
+            }
+            res => res?,
+        }
+    }
+    Ok(())
+}
+
+/// Focus the neighbor matching `targets` within `tree`, announcing it if requested.
+/// If `retrying` is set, the navigation isn't recorded when the first part of the command
+/// is rejected, as it is recorded by the retry instead.
+fn focus_neighbor(
+    c: &mut Connection,
+    tree: &swayipc::Node,
+    args: &[String],
+    flags: &Flags,
+    targets: &[Target],
+    retrying: bool,
+) -> Result<(), FocusError> {
+    let found = tree_command(tree, targets, flags)?;
+    let (neighbor, mut focus_cmd) = found.unzip();
+    let recorded = focus_cmd.clone();
+    profile::lap("search");
+    if focus_cmd.is_none() {
+        focus_cmd = empty_command(c, targets, flags)?;
+    }
+    if focus_cmd.is_none() && flags.fallback_native {
+        // Fall back to the direction of the first target that has one
+        focus_cmd = targets
+            .iter()
+            .find(|t| !parse::is_bare(t.kind))
+            .and_then(|t| {
+                DIRECTIONS
+                    .iter()
+                    .find(|(_, b, v)| (*b, *v) == (t.backward, t.vertical))
+            })
+            .map(|(name, _, _)| format!("focus {name}"));
+    }
+    let res = focus_cmd.map_or(Ok(()), |focus_cmd| {
         info!("Running focus command: '{focus_cmd}'");
-        c.run_command(focus_cmd).map_err(FocusError::SwayIPC)?;
+        run_command(c, &focus_cmd)
+    });
+    profile::lap("run command");
+    let retried = retrying && matches!(res, Err(FocusError::Rejected(_, _, 0)));
+    if let (Some(dir), false) = (&flags.record, retried) {
+        let path = record::save(dir, args, tree, recorded.as_deref()).map_err(FocusError::Io)?;
+        info!("Recorded navigation to {path:?}");
+    }
+    res?;
+    if let (true, Some(neighbor)) = (flags.tick, neighbor) {
+        let kinds: Vec<&str> = targets.iter().map(|t| parse::kind_name(t.kind)).collect();
+        let payload = serde_json::json!({
+            "source": "sway-overfocus",
+            "from": tree.find_as_ref(|n| n.focused).map(|n| n.id),
+            "to": neighbor.id,
+            "type": neighbor.node_type,
+            "targets": kinds,
+        });
+        info!("Sending tick '{payload}'");
+        c.send_tick(payload.to_string())
+            .map_err(FocusError::SwayIPC)?;
+    }
+    if let (Some(announce), Some(neighbor)) = (&flags.announce, neighbor) {
+        let name = tree::focused_leaf(neighbor)
+            .name
+            .as_deref()
+            .unwrap_or_default();
+        info!("Announcing '{name}'");
+        // The name is passed in the environment, so it is never interpreted by the shell
+        std::process::Command::new("sh")
+            .args(["-c", announce])
+            .env("OVERFOCUS_TITLE", name)
+            .spawn()
+            .map_err(FocusError::Io)?;
+    }
+    Ok(())
+}
+
+/// Move the focused window to the scratchpad and focus the neighbor matching `args`.
+fn scratchpad(args: &[String]) -> Result<(), FocusError> {
+    let (flags, args) = parse_flags(args)?;
+    let targets = parse_targets(args)?;
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::Full, &flags)?;
+
+    let focused = tree.find_as_ref(|n| n.focused).ok_or(FocusError::Command)?;
+    let mut cmd = format!("[con_id={}] move scratchpad", focused.id);
+    if let Some(focus_cmd) = neighbor_command(&mut c, &tree, &targets, &flags)? {
+        cmd = format!("{cmd}; {focus_cmd}");
+    }
+    info!("Running command: '{cmd}'");
+    run_command(&mut c, &cmd)?;
+    Ok(())
+}
+
+/// Focus the `n`th visible window of the focused workspace, in left-to-right order.
+fn goto(args: &[String]) -> Result<(), FocusError> {
+    let Some((n, args)) = args.split_first() else {
+        return Err(FocusError::Args);
+    };
+    let n: usize = n.parse().map_err(|_| FocusError::Args)?;
+    let (flags, args) = parse_flags(args)?;
+    if !args.is_empty() {
+        return Err(FocusError::Args);
+    }
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::FocusedWorkspace, &flags)?;
+
+    let window = algorithm::nth_window(&tree, n).ok_or(FocusError::NoNeighbor)?;
+    let cmd = tree::focus_command(window, &flags.command).ok_or(FocusError::Command)?;
+    info!("Running focus command: '{cmd}'");
+    run_command(&mut c, &cmd)?;
+    Ok(())
+}
+
+/// Exchange the visible workspaces of the focused output and its neighbor in a direction.
+/// Focus stays with the focused workspace.
+fn swap_outputs(args: &[String]) -> Result<(), FocusError> {
+    let Some((direction, args)) = args.split_first() else {
+        return Err(FocusError::Args);
+    };
+    let (backward, vertical) = parse_direction(direction)?;
+    let (flags, args) = parse_flags(args)?;
+    if !args.is_empty() {
+        return Err(FocusError::Args);
+    }
+    let target = Target::new(Kind::Output)
+        .backward(backward)
+        .vertical(vertical);
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::Full, &flags)?;
+
+    // Workspaces are resolved to their focused windows, so look up the workspaces containing them
+    let workspace_of = |n: &swayipc::Node| {
+        let workspaces = tree
+            .nodes
+            .iter()
+            .flat_map(|o| o.nodes.iter().map(move |w| (o, w)));
+        let mut found = workspaces.filter(|(_, w)| w.find_as_ref(|c| c.id == n.id).is_some());
+        found.next()
+    };
+    let focused = tree::focused_leaf(&tree);
+    let Some(neighbor) = algorithm::neighbor(&tree, &[target], &flags.options) else {
+        info!("No neighboring output");
+        return Ok(());
+    };
+    let (Some((a, focused)), Some((b, neighbor))) = (workspace_of(focused), workspace_of(neighbor))
+    else {
+        return Err(FocusError::Command);
+    };
+    let (Some(a), Some(b)) = (&a.name, &b.name) else {
+        return Err(FocusError::Command);
+    };
+    let (Some(focused_name), Some(neighbor_name)) = (&focused.name, &neighbor.name) else {
+        return Err(FocusError::Command);
+    };
+    // Switching to a workspace must not be redirected back and forth,
+    // or the wrong workspace is moved
+    let options = tree::CommandOptions {
+        no_auto_back_and_forth: true,
+        ..flags.command
+    };
+    let focused_cmd = tree::workspace_command(focused_name, false, &options);
+    let neighbor_cmd = tree::workspace_command(neighbor_name, false, &options);
+    let cmd = format!(
+        "{focused_cmd}; move workspace to output {b}; \
+         {neighbor_cmd}; move workspace to output {a}; \
+         {focused_cmd}"
+    );
+    info!("Running swap command: '{cmd}'");
+    run_command(&mut c, &cmd)?;
+    Ok(())
+}
+
+/// Give all children of the innermost split matching `args` the same size.
+fn balance(args: &[String]) -> Result<(), FocusError> {
+    let (flags, args) = parse_flags(args)?;
+    let targets = parse_targets(args)?;
+    // Other kinds match containers that aren't splits, fx. workspaces holding only floats
+    if let Some(target) = targets.iter().find(|t| t.kind != Kind::Split) {
+        let arg = args.join(" ");
+        return Err(FocusError::Parse(parse::ParseError {
+            len: arg.len(),
+            arg,
+            start: 0,
+            message: format!(
+                "'balance' only accepts split targets, not '{}'",
+                parse::kind_name(target.kind)
+            ),
+            suggestion: None,
+        }));
+    }
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::of(&targets), &flags)?;
+
+    let split = algorithm::matching_ancestor(&tree, &targets).filter(|n| !n.nodes.is_empty());
+    let Some(split) = split else {
+        info!("No matching container");
+        return Ok(());
+    };
+    let dimension = match split.layout {
+        swayipc::NodeLayout::SplitH => "width",
+        swayipc::NodeLayout::SplitV => "height",
+        _ => return Err(FocusError::Command),
+    };
+    let percent = 100 / split.nodes.len();
+    let cmds: Vec<String> = split
+        .nodes
+        .iter()
+        .map(|n| format!("[con_id={}] resize set {dimension} {percent} ppt", n.id))
+        .collect();
+    let cmd = cmds.join("; ");
+    info!("Running resize command: '{cmd}'");
+    run_command(&mut c, &cmd)?;
+    Ok(())
+}
+
+/// Toggle the innermost container matching `args` between grouped and split layouts,
+/// tabbed with horizontal splits and stacked with vertical splits.
+fn toggle_layout(args: &[String]) -> Result<(), FocusError> {
+    let (flags, args) = parse_flags(args)?;
+    let targets = parse_targets(args)?;
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::of(&targets), &flags)?;
+
+    let Some(container) = algorithm::matching_ancestor(&tree, &targets) else {
+        info!("No matching container");
+        return Ok(());
+    };
+    let layout = match container.layout {
+        swayipc::NodeLayout::Tabbed => "splith",
+        swayipc::NodeLayout::SplitH => "tabbed",
+        swayipc::NodeLayout::Stacked => "splitv",
+        swayipc::NodeLayout::SplitV => "stacking",
+        _ => return Err(FocusError::Command),
+    };
+    // Sway applies `layout` to the parent of the selected container,
+    // so the command selects the most recently focused tiled child
+    let child = container
+        .focus
+        .iter()
+        .find_map(|&id| container.nodes.iter().find(|n| n.id == id))
+        .ok_or(FocusError::Command)?;
+    let cmd = format!("[con_id={}] layout {layout}", child.id);
+    info!("Running layout command: '{cmd}'");
+    run_command(&mut c, &cmd)?;
+    Ok(())
+}
+
+/// Keep focusing windows as they become urgent, optionally only those of the given apps.
+/// After focusing a window, urgent windows are ignored until the cooldown has passed.
+fn focus_urgent(args: &[String]) -> Result<(), FocusError> {
+    let mut apps = Vec::new();
+    let mut cooldown = Duration::ZERO;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let invalid = || FocusError::FlagValue(arg.clone());
+        match arg.as_str() {
+            "--app" => apps.push(args.next().ok_or_else(invalid)?.as_str()),
+            "--cooldown" => {
+                let ms = args.next().and_then(|ms| ms.parse().ok());
+                cooldown = Duration::from_millis(ms.ok_or_else(invalid)?);
+            }
+            _ => return Err(FocusError::Flag(arg.clone())),
+        }
+    }
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    info!("Subscribing to events");
+    let events = Connection::new()
+        .and_then(|c| c.subscribe([EventType::Window]))
+        .map_err(FocusError::SwayIPC)?;
+    let mut last_focus: Option<Instant> = None;
+    for event in events {
+        let Event::Window(event) = event.map_err(FocusError::SwayIPC)? else {
+            continue;
+        };
+        let window = &event.container;
+        if event.change != WindowChange::Urgent || !window.urgent || window.focused {
+            continue;
+        }
+        let app = tree::app(window);
+        if !apps.is_empty() && !app.is_some_and(|app| apps.contains(&app)) {
+            info!("Ignoring urgent window {} of app {app:?}", window.id);
+            continue;
+        }
+        if last_focus.is_some_and(|t| t.elapsed() < cooldown) {
+            info!("Ignoring urgent window {} during cooldown", window.id);
+            continue;
+        }
+        let cmd = format!("[con_id={}] focus", window.id);
+        info!("Running focus command: '{cmd}'");
+        match run_command(&mut c, &cmd) {
+            // The window may have closed in the meantime
+            Err(FocusError::Rejected(_, msg, _)) => warn!("Failed to focus urgent window: {msg}"),
+            res => res?,
+        }
+        last_focus = Some(Instant::now());
+    }
+    Ok(())
+}
+
+/// Focus a window matching sway criteria, or run a command if there is none.
+fn raise(args: &[String]) -> Result<(), FocusError> {
+    let [criteria, sep, command @ ..] = args else {
+        return Err(FocusError::Args);
+    };
+    if sep != "--" || command.is_empty() {
+        return Err(FocusError::Args);
+    }
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let cmd = format!("{criteria} focus");
+    info!("Running focus command: '{cmd}'");
+    match run_command(&mut c, &cmd) {
+        Err(FocusError::Rejected(_, msg, _)) => {
+            info!("No window focused ('{msg}'), running command instead");
+            std::process::Command::new(&command[0])
+                .args(&command[1..])
+                .spawn()
+                .map_err(FocusError::Io)?;
+            Ok(())
+        }
+        res => res,
+    }
+}
+
+/// Focus the window whose title or app ID best matches a fuzzy query.
+/// If several windows match equally well, they are printed instead.
+fn find(args: &[String]) -> Result<(), FocusError> {
+    let Some((query, args)) = args.split_first() else {
+        return Err(FocusError::Args);
+    };
+    let (flags, args) = parse_flags(args)?;
+    if !args.is_empty() {
+        return Err(FocusError::Args);
+    }
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::Full, &flags)?;
+
+    let windows = tree::leaves(&tree)
+        .into_iter()
+        .filter(|n| matches!(n.node_type, NodeType::Con | NodeType::FloatingCon));
+    let matches: Vec<(i64, &swayipc::Node)> = windows
+        .filter_map(|n| {
+            let fields = [n.name.as_deref(), n.app_id.as_deref()];
+            let scores = fields.into_iter().flatten().map(|f| fuzzy::score(query, f));
+            Some((scores.flatten().max()?, n))
+        })
+        .collect();
+    let Some(best) = matches.iter().map(|(score, _)| *score).max() else {
+        return Err(FocusError::NoNeighbor);
+    };
+    let best: Vec<&swayipc::Node> = matches
+        .into_iter()
+        .filter(|(score, _)| *score == best)
+        .map(|(_, n)| n)
+        .collect();
+    if let [window] = best[..] {
+        let cmd = tree::focus_command(window, &flags.command).ok_or(FocusError::Command)?;
+        info!("Running focus command: '{cmd}'");
+        run_command(&mut c, &cmd)?;
     } else {
-        info!("No neighbor found");
+        for window in best {
+            println!("{}", tree::describe(window));
+        }
     }
     Ok(())
 }
 
-fn parse_args(args: &[String]) -> Option<Box<[Target]>> {
-    if args.len() < 2 {
-        return None;
+/// Focus the topmost visible window at a point,
+/// given as two arguments or selected with `slurp` when `--interactive` is given.
+fn focus_at(args: &[String]) -> Result<(), FocusError> {
+    let (point, args) = match args {
+        [flag, rest @ ..] if flag == "--interactive" => (select_point()?, rest),
+        [x, y, rest @ ..] => {
+            let coord = |s: &String| s.parse().map_err(|_| FocusError::Args);
+            ((coord(x)?, coord(y)?), rest)
+        }
+        _ => return Err(FocusError::Args),
+    };
+    let (flags, args) = parse_flags(args)?;
+    if !args.is_empty() {
+        return Err(FocusError::Args);
+    }
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::Full, &flags)?;
+
+    let (x, y) = point;
+    let window = algorithm::window_at(&tree, &tree::Vec2 { x, y }).ok_or(FocusError::NoNeighbor)?;
+    let cmd = tree::focus_command(window, &flags.command).ok_or(FocusError::Command)?;
+    info!("Running focus command: '{cmd}'");
+    run_command(&mut c, &cmd)?;
+    Ok(())
+}
+
+/// Let the user select a point with `slurp -p`.
+/// Fails silently if the selection is cancelled.
+fn select_point() -> Result<(i32, i32), FocusError> {
+    info!("Selecting point with slurp");
+    let output = std::process::Command::new("slurp")
+        .args(["-p", "-f", "%x %y"])
+        .output()
+        .map_err(FocusError::Io)?;
+    if !output.status.success() {
+        return Err(FocusError::NoNeighbor);
+    }
+    let output = String::from_utf8_lossy(&output.stdout);
+    let mut coords = output.split_whitespace().map(str::parse);
+    match (coords.next(), coords.next()) {
+        (Some(Ok(x)), Some(Ok(y))) => Ok((x, y)),
+        _ => Err(FocusError::Command),
+    }
+}
+
+/// Focus the nearest ancestor of the focused container, or the nearest descendant on
+/// the focus path, skipping wrapper containers. Ancestors beyond the workspace are not focused.
+fn focus_level(args: &[String], up: bool) -> Result<(), FocusError> {
+    let (mut flags, args) = parse_flags(args)?;
+    if !args.is_empty() {
+        return Err(FocusError::Args);
     }
+    // Collapsing would hide the focused container if it is a wrapper itself
+    flags.no_collapse = true;
 
-    args[1..]
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::FocusedWorkspace, &flags)?;
+
+    let path = tree::focus_path(&tree);
+    let ws_idx = path
         .iter()
-        .map(|arg| {
-            let (target_name, mode_chars) = arg.split_once('-')?;
-            let kind = match target_name {
-                "split" => Some(Kind::Split),
-                "group" => Some(Kind::Group),
-                "float" => Some(Kind::Float),
-                "workspace" => Some(Kind::Workspace),
-                "output" => Some(Kind::Output),
-                _ => None,
-            }?;
-            let mut mode_chars = mode_chars.chars();
-            let (backward, vertical) = match mode_chars.next()? {
-                'r' => Some((false, false)),
-                'l' => Some((true, false)),
-                'd' => Some((false, true)),
-                'u' => Some((true, true)),
-                _ => None,
-            }?;
-            let edge_mode = match mode_chars.next()? {
-                's' => Some(EdgeMode::Stop),
-                'w' => Some(EdgeMode::Wrap),
-                't' => Some(EdgeMode::Traverse),
-                'i' => Some(EdgeMode::Inactive),
-                _ => None,
-            }?;
-            Some(Target {
-                kind,
-                backward,
-                vertical,
-                edge_mode,
-            })
+        .position(|n| n.node_type == NodeType::Workspace)
+        .ok_or(FocusError::NoNeighbor)?;
+    let cmd = if up {
+        let idx = (ws_idx..path.len() - 1)
+            .rev()
+            .find(|&idx| idx == ws_idx || !tree::is_wrapper(path[idx], path[idx - 1]))
+            .ok_or(FocusError::NoNeighbor)?;
+        // Criteria don't match workspaces, so the levels are climbed one at a time
+        vec!["focus parent"; path.len() - 1 - idx].join("; ")
+    } else {
+        let mut parent = path[path.len() - 1];
+        let mut child = tree::focus_local(parent);
+        while let Some(node) = child.filter(|n| tree::is_wrapper(n, parent)) {
+            parent = node;
+            child = tree::focus_local(node);
+        }
+        let target = child.ok_or(FocusError::NoNeighbor)?;
+        format!("[con_id={}] focus", target.id)
+    };
+    info!("Running focus command: '{cmd}'");
+    run_command(&mut c, &cmd)?;
+    Ok(())
+}
+
+/// Print information about the focused container.
+/// Currently only `layout` is supported, which prints the layout of the parent of the focused
+/// container, or `floating` if it is floating.
+fn query(args: &[String]) -> Result<(), FocusError> {
+    let Some((query, args)) = args.split_first() else {
+        return Err(FocusError::Args);
+    };
+    let (flags, args) = parse_flags(args)?;
+    if query != "layout" || !args.is_empty() {
+        return Err(FocusError::Args);
+    }
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::FocusedWorkspace, &flags)?;
+
+    let mut parent = &tree;
+    while let Some(child) = tree::focus_local(parent) {
+        if child.focused {
+            break;
+        }
+        parent = child;
+    }
+    let focused_floats = tree::focus_idx(parent)
+        .is_some_and(|(_, children)| std::ptr::eq(children, &parent.floating_nodes));
+    let layout = match parent.layout {
+        _ if focused_floats => "floating",
+        swayipc::NodeLayout::SplitH => "splith",
+        swayipc::NodeLayout::SplitV => "splitv",
+        swayipc::NodeLayout::Stacked => "stacked",
+        swayipc::NodeLayout::Tabbed => "tabbed",
+        _ => "none",
+    };
+    let fields = vec![("layout", layout.into())];
+    let format = flags.output_format.unwrap_or(output::Format::Plain);
+    output::print(format, vec![(fields, layout.to_string())]);
+    Ok(())
+}
+
+/// Print the visible windows, or all windows with `--all`, as JSON by default.
+fn list(args: &[String]) -> Result<(), FocusError> {
+    let (all, args) = match args.split_first() {
+        Some((flag, rest)) if flag == "--all" => (true, rest),
+        _ => (false, args),
+    };
+    let (flags, args) = parse_flags(args)?;
+    if !args.is_empty() {
+        return Err(FocusError::Args);
+    }
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::Full, &flags)?;
+
+    let mut windows = Vec::new();
+    for output in &tree.nodes {
+        let visible = tree::focus_local(output).map(|w| w.id);
+        for workspace in &output.nodes {
+            let leaves: Vec<&swayipc::Node> = if all {
+                tree::leaves(workspace)
+            } else if visible == Some(workspace.id) {
+                let children = workspace.nodes.iter().chain(&workspace.floating_nodes);
+                children.flat_map(tree::visible_leaves).collect()
+            } else {
+                Vec::new()
+            };
+            let leaves = leaves
+                .into_iter()
+                .filter(|n| matches!(n.node_type, NodeType::Con | NodeType::FloatingCon));
+            windows.extend(leaves.map(|n| (n, workspace, output)));
+        }
+    }
+
+    let results = windows
+        .into_iter()
+        .map(|(n, workspace, output)| {
+            let r = &n.rect;
+            let fields = vec![
+                ("con_id", n.id.into()),
+                ("name", n.name.clone().into()),
+                ("app_id", n.app_id.clone().into()),
+                ("workspace", workspace.name.clone().into()),
+                ("output", output.name.clone().into()),
+                ("x", r.x.into()),
+                ("y", r.y.into()),
+                ("width", r.width.into()),
+                ("height", r.height.into()),
+            ];
+            let line = format!("{} on {}", tree::describe(n), tree::describe(workspace));
+            (fields, line)
+        })
+        .collect();
+    output::print(flags.output_format.unwrap_or(output::Format::Json), results);
+    Ok(())
+}
+
+/// Check that sway can be reached and understood, printing the result of each step.
+fn doctor() -> Result<(), FocusError> {
+    let mut failed = 0;
+    let mut check = |name: &str, result: Result<String, String>| match result {
+        Ok(info) => println!("ok: {name}: {info}"),
+        Err(e) => {
+            failed += 1;
+            println!("error: {name}: {e}");
+        }
+    };
+
+    let socket = ["I3SOCK", "SWAYSOCK"]
+        .iter()
+        .find_map(|var| Some((var, std::env::var(var).ok()?)));
+    check(
+        "socket",
+        match &socket {
+            Some((var, path)) if std::path::Path::new(path).exists() => Ok(format!("{var}={path}")),
+            Some((var, path)) => Err(format!("{var} is set to '{path}', which doesn't exist")),
+            None => Err("neither SWAYSOCK nor I3SOCK is set, is sway running?".to_string()),
+        },
+    );
+
+    let mut c = match Connection::new() {
+        Ok(c) => {
+            check("connect", Ok("connected".to_string()));
+            c
+        }
+        Err(e) => {
+            check("connect", Err(e.to_string()));
+            return Err(FocusError::Doctor(failed));
+        }
+    };
+    check(
+        "version",
+        c.get_version()
+            .map(|v| v.human_readable)
+            .map_err(|e| e.to_string()),
+    );
+    check(
+        "tree",
+        c.get_tree()
+            .map(tree::preprocess)
+            .map(|tree| format!("{} nodes", tree.iter().count()))
+            .map_err(|e| e.to_string()),
+    );
+    let focused = c
+        .get_workspaces()
+        .map_err(|e| e.to_string())
+        .and_then(|workspaces| {
+            let focused = workspaces.iter().find(|w| w.focused);
+            focused
+                .map(|w| w.id)
+                .ok_or("no focused workspace".to_string())
+        });
+    check(
+        "partial tree",
+        focused.and_then(|id| {
+            let tree = ipc::get_tree_partial(&mut c, id).map_err(|e| e.to_string())?;
+            Ok(format!("{} nodes", tree.iter().count()))
+        }),
+    );
+    check(
+        "command",
+        match c.run_command("nop") {
+            Ok(results) => match results.into_iter().find_map(Result::err) {
+                Some(e) => Err(e.to_string()),
+                None => Ok("ran 'nop'".to_string()),
+            },
+            Err(e) => Err(e.to_string()),
+        },
+    );
+
+    match failed {
+        0 => Ok(()),
+        n => Err(FocusError::Doctor(n)),
+    }
+}
+
+/// Move the focused float in a direction until it meets an edge of the workspace
+/// or of another float.
+fn snap(args: &[String]) -> Result<(), FocusError> {
+    let Some((direction, args)) = args.split_first() else {
+        return Err(FocusError::Args);
+    };
+    let (backward, vertical) = parse_direction(direction)?;
+    let (flags, args) = parse_flags(args)?;
+    if !args.is_empty() {
+        return Err(FocusError::Args);
+    }
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::FocusedWorkspace, &flags)?;
+
+    let path = tree::focus_path(&tree);
+    let ws_idx = path
+        .iter()
+        .position(|n| n.node_type == NodeType::Workspace)
+        .ok_or(FocusError::Command)?;
+    let (ws, float) = match path.get(ws_idx + 1) {
+        Some(float) if float.node_type == NodeType::FloatingCon => (path[ws_idx], *float),
+        _ => {
+            info!("Focused window isn't floating");
+            return Ok(());
+        }
+    };
+    let Some(pos) = algorithm::snap_position(ws, float, backward, vertical) else {
+        info!("No edge to snap to");
+        return Ok(());
+    };
+    let (x, y) = match vertical {
+        true => (float.rect.x, pos),
+        false => (pos, float.rect.y),
+    };
+    let cmd = format!("[con_id={}] move absolute position {x} {y}", float.id);
+    info!("Running move command: '{cmd}'");
+    run_command(&mut c, &cmd)?;
+    Ok(())
+}
+
+/// Move the focused window out of its parent container,
+/// placing it next to the container on the side given by a direction.
+fn break_out(args: &[String]) -> Result<(), FocusError> {
+    let Some((direction, args)) = args.split_first() else {
+        return Err(FocusError::Args);
+    };
+    let (backward, vertical) = parse_direction(direction)?;
+    let (mut flags, args) = parse_flags(args)?;
+    if !args.is_empty() {
+        return Err(FocusError::Args);
+    }
+    // Collapsing could replace the parent with the grandparent, miscounting the siblings
+    flags.no_collapse = true;
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::FocusedWorkspace, &flags)?;
+
+    let focused = tree::focused_container(&tree, false).ok_or(FocusError::Command)?;
+    let parent = tree::focused_container(&tree, true).and_then(|parent| {
+        let idx = parent.nodes.iter().position(|n| n.id == focused.id)?;
+        Some((parent, idx))
+    });
+    let Some((parent, idx)) = parent else {
+        info!("Focused window isn't tiled inside a container");
+        return Ok(());
+    };
+    // Sway moves a window out of its container once it is moved past the edge,
+    // so the window is first moved past its siblings on that side.
+    let aligned = match parent.layout {
+        swayipc::NodeLayout::SplitH | swayipc::NodeLayout::Tabbed => !vertical,
+        swayipc::NodeLayout::SplitV | swayipc::NodeLayout::Stacked => vertical,
+        _ => false,
+    };
+    let siblings = match (aligned, backward) {
+        (false, _) => 0,
+        (true, true) => idx,
+        (true, false) => parent.nodes.len() - 1 - idx,
+    };
+    let moves = vec![format!("[con_id={}] move {direction}", focused.id); siblings + 1];
+    let cmd = moves.join("; ");
+    info!("Running move command: '{cmd}'");
+    run_command(&mut c, &cmd)?;
+    Ok(())
+}
+
+/// Exit with an error code if no neighbor matching `args` exists, without changing focus.
+fn can_focus(args: &[String]) -> Result<(), FocusError> {
+    let (flags, args) = parse_flags(args)?;
+    let targets = parse_targets(args)?;
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::of(&targets), &flags)?;
+
+    neighbor_command(&mut c, &tree, &targets, &flags)?.ok_or(FocusError::NoNeighbor)?;
+    Ok(())
+}
+
+/// Search for a neighbor and generate a command to focus it, if any is found.
+fn neighbor_command(
+    c: &mut Connection,
+    tree: &swayipc::Node,
+    targets: &[Target],
+    flags: &Flags,
+) -> Result<Option<String>, FocusError> {
+    match tree_command(tree, targets, flags)? {
+        Some((_, focus_cmd)) => Ok(Some(focus_cmd)),
+        None => empty_command(c, targets, flags),
+    }
+}
+
+/// Search `tree` for a neighbor and generate a command to focus it, if any is found.
+/// The neighbor is returned along with the command.
+fn tree_command<'a>(
+    tree: &'a swayipc::Node,
+    targets: &[Target],
+    flags: &Flags,
+) -> Result<Option<(&'a swayipc::Node, String)>, FocusError> {
+    info!("Searching for neighbor");
+    let Some(neighbor) = algorithm::neighbor(tree, targets, &flags.options) else {
+        return Ok(None);
+    };
+    let focus_cmd = tree::focus_command(neighbor, &flags.command).ok_or(FocusError::Command)?;
+    info!(neighbor = neighbor.id, command = focus_cmd.as_str(); "Decided on neighbor");
+    Ok(Some((neighbor, focus_cmd)))
+}
+
+/// Generate a command to create a new workspace for an `empty` target, if there is one.
+fn empty_command(
+    c: &mut Connection,
+    targets: &[Target],
+    flags: &Flags,
+) -> Result<Option<String>, FocusError> {
+    if let Some(target) = targets.iter().find(|t| t.kind == Kind::Empty) {
+        info!("No neighbor found, creating empty workspace");
+        let workspaces = c.get_workspaces().map_err(FocusError::SwayIPC)?;
+        let Some(num) = algorithm::new_workspace(&workspaces, target) else {
+            return Ok(None);
+        };
+        let cmd = match &flags.workspace_name {
+            Some(pattern) => {
+                let name = pattern.replace("{n}", &num.to_string());
+                tree::workspace_command(&name, false, &flags.command)
+            }
+            None => tree::workspace_command(&num.to_string(), true, &flags.command),
+        };
+        return Ok(Some(cmd));
+    }
+    info!("No neighbor found");
+    Ok(None)
+}
+
+/// Print the neighbor that would be focused in each direction.
+/// The directions of the targets in `args` are ignored.
+fn print_candidates(args: &[String]) -> Result<(), FocusError> {
+    let (flags, args) = parse_flags(args)?;
+    let targets = parse_targets(args)?;
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::of(&targets), &flags)?;
+
+    let mut results = Vec::new();
+    for (name, backward, vertical) in DIRECTIONS {
+        info!("Searching for neighbor to the {name}");
+        let targets = with_direction(&targets, backward, vertical);
+        let neighbor = algorithm::neighbor(&tree, &targets, &flags.options);
+        let fields = vec![
+            ("direction", name.into()),
+            ("con_id", neighbor.map(|n| n.id).into()),
+            (
+                "type",
+                neighbor
+                    .and_then(|n| serde_json::to_value(n.node_type).ok())
+                    .into(),
+            ),
+            ("name", neighbor.and_then(|n| n.name.clone()).into()),
+        ];
+        let line = match neighbor {
+            Some(neighbor) => format!("{name}: {}", tree::describe(neighbor)),
+            None => format!("{name}: none"),
+        };
+        results.push((fields, line));
+    }
+    output::print(
+        flags.output_format.unwrap_or(output::Format::Plain),
+        results,
+    );
+    Ok(())
+}
+
+/// Print the pre-processed tree as JSON, as a DOT graph, or as an indented text tree.
+/// If targets are given, the neighbor matching them is highlighted in the graph and text tree.
+fn dump_tree(args: &[String]) -> Result<(), FocusError> {
+    let (format, args) = match args.split_first() {
+        Some((flag, rest)) if flag == "--dot" => ("dot", rest),
+        Some((flag, rest)) if flag == "--pretty" => ("pretty", rest),
+        _ => ("json", args),
+    };
+    let (flags, args) = parse_flags(args)?;
+    let targets = parse::parse_targets(args).map_err(FocusError::Parse)?;
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let tree = get_tree(&mut c, Scope::Full, &flags)?;
+
+    let search = algorithm::search(&tree, &targets, &flags.options);
+    let neighbor = search.neighbor.map(|n| n.id);
+    match format {
+        "dot" => println!("{}", dot::render(&tree, neighbor)),
+        "pretty" => {
+            let ancestor = search.ancestor.map(|n| n.id);
+            print!("{}", pretty::render(&tree, ancestor, neighbor));
+        }
+        _ => println!(
+            "{}",
+            serde_json::to_string(&tree).expect("tree is serializable")
+        ),
+    }
+    Ok(())
+}
+
+/// Re-run the navigations recorded in a directory and report those with a different result.
+fn replay(args: &[String]) -> Result<(), FocusError> {
+    let [dir] = args else {
+        return Err(FocusError::Args);
+    };
+    let cases = record::load(dir.as_ref()).map_err(FocusError::Io)?;
+    let mut differing = 0;
+    for (name, case) in &cases {
+        info!("Replaying {name}");
+        let (flags, targets) = parse_flags(&case.args)?;
+        let targets = parse_targets(targets)?;
+        let command = tree_command(&case.tree, &targets, &flags)?.map(|(_, cmd)| cmd);
+        if command == case.command {
+            println!("{name}: ok");
+        } else {
+            differing += 1;
+            println!("{name}: expected {:?}, got {:?}", case.command, command);
+        }
+    }
+    match differing {
+        0 => Ok(()),
+        n => Err(FocusError::Replay(n)),
+    }
+}
+
+/// Shrink the tree of a recorded case as far as possible without changing the resulting command,
+/// printing the minimized case. Containers on the focus path are kept.
+fn minimize(args: &[String]) -> Result<(), FocusError> {
+    let [path] = args else {
+        return Err(FocusError::Args);
+    };
+    let mut case = record::load_case(path.as_ref()).map_err(FocusError::Io)?;
+    let (flags, targets) = parse_flags(&case.args)?;
+    let targets = parse_targets(targets)?;
+    let command = |tree: &swayipc::Node| -> Result<Option<String>, FocusError> {
+        Ok(tree_command(tree, &targets, &flags)?.map(|(_, cmd)| cmd))
+    };
+    let observed = command(&case.tree)?;
+    info!("Observed command {observed:?}");
+
+    // Remove one node at a time, until no node can be removed
+    let mut shrunk = true;
+    while shrunk {
+        shrunk = false;
+        let kept: Vec<i64> = tree::focus_path(&case.tree).iter().map(|n| n.id).collect();
+        let ids: Vec<i64> = case.tree.iter().map(|n| n.id).collect();
+        for id in ids.into_iter().filter(|id| !kept.contains(id)) {
+            let mut tree = case.tree.clone();
+            if tree::remove_node(&mut tree, id) && command(&tree)? == observed {
+                info!("Removed node {id}");
+                case.tree = tree;
+                shrunk = true;
+            }
+        }
+    }
+    case.command = observed;
+    println!("{}", record::to_json(&case));
+    Ok(())
+}
+
+/// Print a waybar module payload showing the directions in which a neighbor exists.
+/// With `--follow`, a new payload is printed whenever the available directions change.
+/// The directions of the targets in `args` are ignored.
+fn waybar(args: &[String]) -> Result<(), FocusError> {
+    let (flags, args) = parse_flags(args)?;
+    let targets = parse_targets(args)?;
+
+    info!("Starting connection");
+    let mut c = Connection::new().map_err(FocusError::SwayIPC)?;
+    let scope = Scope::of(&targets);
+    let mut payload = waybar_payload(&get_tree(&mut c, scope, &flags)?, &targets, &flags.options);
+    println!("{payload}");
+    if !flags.follow {
+        return Ok(());
+    }
+
+    info!("Subscribing to events");
+    let events = Connection::new()
+        .and_then(|c| c.subscribe([EventType::Window, EventType::Workspace, EventType::Output]))
+        .map_err(FocusError::SwayIPC)?;
+    for event in events {
+        event.map_err(FocusError::SwayIPC)?;
+        let new_payload =
+            waybar_payload(&get_tree(&mut c, scope, &flags)?, &targets, &flags.options);
+        if new_payload != payload {
+            payload = new_payload;
+            println!("{payload}");
+        }
+    }
+    Ok(())
+}
+
+/// Generate a waybar JSON payload listing the directions in which `targets` find a neighbor.
+fn waybar_payload(tree: &swayipc::Node, targets: &[Target], options: &Options) -> String {
+    let mut arrows = Vec::new();
+    let mut classes = Vec::new();
+    for ((name, backward, vertical), arrow) in DIRECTIONS.into_iter().zip(["←", "→", "↑", "↓"])
+    {
+        if algorithm::neighbor(tree, &with_direction(targets, backward, vertical), options)
+            .is_some()
+        {
+            arrows.push(arrow);
+            classes.push(format!("\"{name}\""));
+        }
+    }
+    format!(
+        "{{\"text\": \"{}\", \"class\": [{}]}}",
+        arrows.join(" "),
+        classes.join(", ")
+    )
+}
+
+/// Names of directions along with their `backward` and `vertical` values.
+const DIRECTIONS: [(&str, bool, bool); 4] = [
+    ("left", true, false),
+    ("right", false, false),
+    ("up", true, true),
+    ("down", false, true),
+];
+
+/// Look up the `backward` and `vertical` values of the direction `name`.
+fn parse_direction(name: &str) -> Result<(bool, bool), FocusError> {
+    let (_, backward, vertical) = DIRECTIONS
+        .into_iter()
+        .find(|(n, _, _)| *n == name)
+        .ok_or(FocusError::Args)?;
+    Ok((backward, vertical))
+}
+
+/// Replace the directions of `targets`.
+fn with_direction(targets: &[Target], backward: bool, vertical: bool) -> Box<[Target]> {
+    targets
+        .iter()
+        .map(|t| Target {
+            backward,
+            vertical,
+            ..*t
         })
         .collect()
 }
+
+/// Print a binding mode block for the sway config.
+fn binding_mode(args: &[String]) -> Result<(), FocusError> {
+    let mut keys = bindings::parse_keys("vim");
+    let mut mode = "focus";
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--keys" => keys = bindings::parse_keys(args.next().ok_or(FocusError::Args)?),
+            "--mode" => mode = args.next().ok_or(FocusError::Args)?,
+            _ => return Err(FocusError::Args),
+        }
+    }
+    let keys = keys.ok_or(FocusError::Args)?;
+    println!("{}", bindings::binding_mode(mode, &keys));
+    Ok(())
+}
+
+/// Parse target arguments, requiring at least one.
+fn parse_targets(args: &[String]) -> Result<Box<[Target]>, FocusError> {
+    if args.is_empty() {
+        return Err(FocusError::Args);
+    }
+    let targets = parse::parse_targets(args).map_err(FocusError::Parse)?;
+    info!(targets:serde = targets; "Parsed targets");
+    Ok(targets)
+}
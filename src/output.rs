@@ -0,0 +1,64 @@
+//! Printing of query results in a format shared between subcommands.
+use serde_json::{Map, Value};
+
+/// Format of printed results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable lines.
+    Plain,
+    /// A JSON array of objects.
+    Json,
+    /// Tab-separated values, one line per result.
+    Tsv,
+}
+
+impl Format {
+    /// Look up a format by name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "plain" => Some(Format::Plain),
+            "json" => Some(Format::Json),
+            "tsv" => Some(Format::Tsv),
+            _ => None,
+        }
+    }
+}
+
+/// Named fields of a single result.
+pub type Fields = Vec<(&'static str, Value)>;
+
+/// Print results, each given as its fields along with its plain text line.
+pub fn print(format: Format, results: Vec<(Fields, String)>) {
+    match format {
+        Format::Plain => {
+            for (_, line) in results {
+                println!("{line}");
+            }
+        }
+        Format::Json => {
+            let objects = results
+                .into_iter()
+                .map(|(fields, _)| {
+                    let fields = fields.into_iter().map(|(k, v)| (k.to_string(), v));
+                    Value::Object(fields.collect::<Map<_, _>>())
+                })
+                .collect();
+            println!("{}", Value::Array(objects));
+        }
+        Format::Tsv => {
+            for (fields, _) in results {
+                let values: Vec<String> = fields.iter().map(|(_, v)| tsv_field(v)).collect();
+                println!("{}", values.join("\t"));
+            }
+        }
+    }
+}
+
+/// Format a value as a single tab-separated field.
+fn tsv_field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.replace(['\t', '\n'], " "),
+        value => value.to_string(),
+    }
+}
@@ -0,0 +1,623 @@
+//! Target parsing and diagnostics.
+use crate::algorithm::{EdgeMode, Kind, Target};
+use std::fmt;
+use std::str::FromStr;
+
+/// Valid target kind names.
+const KINDS: &[(&str, Kind)] = &[
+    ("split", Kind::Split),
+    ("group", Kind::Group),
+    ("float", Kind::Float),
+    ("workspace", Kind::Workspace),
+    ("output", Kind::Output),
+    ("empty", Kind::Empty),
+    ("window", Kind::Window),
+    ("created", Kind::Created),
+    ("urgent", Kind::Urgent),
+];
+
+/// Valid kind names for targets without a direction or edge mode.
+const BARE_KINDS: &[(&str, Kind)] = &[
+    ("layer", Kind::Layer),
+    ("float-any", Kind::FloatAny),
+    ("dialog", Kind::Dialog),
+    ("master", Kind::Master),
+    ("stack", Kind::Stack),
+    ("recent", Kind::Recent),
+    ("newest", Kind::Newest),
+    ("output-prev", Kind::OutputPrev),
+];
+
+/// Name of `kind`, as written in targets.
+pub fn kind_name(kind: Kind) -> &'static str {
+    let mut names = KINDS.iter().chain(BARE_KINDS);
+    names.find(|(_, k)| *k == kind).map_or("", |(name, _)| name)
+}
+
+/// Whether targets of `kind` are given without a direction.
+pub fn is_bare(kind: Kind) -> bool {
+    BARE_KINDS.iter().any(|(_, k)| *k == kind)
+}
+
+/// Valid direction characters, as `(backward, vertical)`.
+const DIRECTIONS: &[(char, (bool, bool))] = &[
+    ('u', (true, true)),
+    ('d', (false, true)),
+    ('l', (true, false)),
+    ('r', (false, false)),
+];
+
+/// Long direction names, along with their characters.
+const DIRECTION_NAMES: &[(&str, char)] =
+    &[("up", 'u'), ("down", 'd'), ("left", 'l'), ("right", 'r')];
+
+/// Valid edge mode characters.
+const EDGE_MODES: &[(char, EdgeMode)] = &[
+    ('s', EdgeMode::Stop),
+    ('w', EdgeMode::Wrap),
+    ('t', EdgeMode::Traverse),
+    ('i', EdgeMode::Inactive),
+];
+
+/// Long edge mode names, along with their characters.
+const EDGE_MODE_NAMES: &[(&str, char)] = &[
+    ("stop", 's'),
+    ("wrap", 'w'),
+    ("traverse", 't'),
+    ("inactive", 'i'),
+];
+
+/// A malformed target argument, along with the location of the error.
+#[derive(Debug)]
+pub struct ParseError {
+    /// The offending argument.
+    pub arg: String,
+    /// Byte offset of the invalid portion.
+    pub start: usize,
+    /// Byte length of the invalid portion.
+    pub len: usize,
+    /// Description of the error.
+    pub message: String,
+    /// Suggested replacement for the invalid portion, if any.
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indent = self.arg[..self.start].chars().count();
+        let width = self.arg[self.start..self.start + self.len].chars().count();
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(f, "    {}", self.arg)?;
+        write!(f, "    {}{}", " ".repeat(indent), "^".repeat(width.max(1)))?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\nhelp: did you mean '{suggestion}'?")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Target {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_target(s)
+    }
+}
+
+/// Writes the target in its short form, which parses back to the same target.
+/// Bare kinds are written without a direction and edge mode, which they ignore,
+/// so those fields are lost for bare targets that weren't parsed from a string.
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", kind_name(self.kind))?;
+        if !is_bare(self.kind) {
+            let dir = DIRECTIONS
+                .iter()
+                .find(|(_, d)| *d == (self.backward, self.vertical));
+            let edge = EDGE_MODES.iter().find(|(_, e)| *e == self.edge_mode);
+            if let (Some((d, _)), Some((e, _))) = (dir, edge) {
+                write!(f, "-{d}{e}")?;
+            }
+        }
+        let mut modifiers = Vec::new();
+        if self.min_children > 0 {
+            modifiers.push(format!("min={}", self.min_children));
+        }
+        if self.distinct_apps {
+            modifiers.push("distinct".to_string());
+        }
+        if !modifiers.is_empty() {
+            write!(f, "({})", modifiers.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a list of target arguments.
+/// Besides single-argument targets, a target can be given as `--kind <kind>`,
+/// followed by `--dir <direction>` and `--edge <edge mode>` in any order.
+pub fn parse_targets(args: &[String]) -> Result<Box<[Target]>, ParseError> {
+    let mut targets = Vec::new();
+    let mut rest = args;
+    while let Some((arg, tail)) = rest.split_first() {
+        rest = tail;
+        if arg != "--kind" {
+            targets.push(parse_target(arg)?);
+            continue;
+        }
+        // Values are kept along with their index in `tail`, for pointing out errors
+        let (mut kind, mut dir, mut edge) = ((0, ""), (0, ""), (0, ""));
+        if let Some((value, tail)) = rest.split_first() {
+            kind = (0, value.as_str());
+            rest = tail;
+        }
+        while let [name, value, after @ ..] = rest {
+            let field = (tail.len() - rest.len() + 1, value.as_str());
+            match name.as_str() {
+                "--dir" => dir = field,
+                "--edge" => edge = field,
+                _ => break,
+            }
+            rest = after;
+        }
+        let long = match BARE_KINDS.iter().any(|(name, _)| *name == kind.1) {
+            true if dir.1.is_empty() && edge.1.is_empty() => kind.1.to_string(),
+            _ => format!("{}:{}:{}", kind.1, dir.1, edge.1),
+        };
+        let target = parse_target(&long).map_err(|e| {
+            // Point out the offending value among the flags rather than in `long`
+            let flags = &tail[..tail.len() - rest.len()];
+            let mut offsets = Vec::new();
+            let mut user = arg.clone();
+            for flag in flags {
+                user.push(' ');
+                offsets.push(user.len());
+                user.push_str(flag);
+            }
+            let field = if e.start <= kind.1.len() {
+                kind
+            } else if e.start <= kind.1.len() + dir.1.len() + 1 {
+                dir
+            } else {
+                edge
+            };
+            let (start, len) = match offsets.get(field.0) {
+                Some(&start) if !field.1.is_empty() => (start, field.1.len()),
+                _ => (user.len(), 0),
+            };
+            ParseError {
+                arg: user,
+                start,
+                len,
+                ..e
+            }
+        })?;
+        targets.push(target);
+    }
+    Ok(targets.into_boxed_slice())
+}
+
+/// Parse a single target of the form `<kind>-<direction><edge mode>`,
+/// or a bare kind name for kinds that don't take a direction.
+/// Either can be followed by modifiers in parentheses, separated by commas,
+/// `min=<n>` and `distinct`.
+pub fn parse_target(arg: &str) -> Result<Target, ParseError> {
+    let Some((base, modifier)) = arg.split_once('(') else {
+        return parse_base_target(arg);
+    };
+    let mut target = parse_base_target(base).map_err(|e| ParseError {
+        arg: arg.to_string(),
+        ..e
+    })?;
+    let invalid = || ParseError {
+        arg: arg.to_string(),
+        start: base.len(),
+        len: arg.len() - base.len(),
+        message: "invalid modifier, expected '(min=<n>)' or '(distinct)'".to_string(),
+        suggestion: None,
+    };
+    let modifiers = modifier.strip_suffix(')').ok_or_else(invalid)?;
+    for modifier in modifiers.split(',') {
+        match modifier.split_once('=') {
+            Some(("min", n)) => target.min_children = n.parse().map_err(|_| invalid())?,
+            None if modifier == "distinct" => target.distinct_apps = true,
+            _ => return Err(invalid()),
+        }
+    }
+    if target.distinct_apps && !matches!(target.kind, Kind::Recent | Kind::Created | Kind::Newest) {
+        return Err(ParseError {
+            message: "'distinct' only applies to recent, created, and newest targets".to_string(),
+            ..invalid()
+        });
+    }
+    Ok(target)
+}
+
+/// Parse a target without modifiers.
+fn parse_base_target(arg: &str) -> Result<Target, ParseError> {
+    if let Some((_, kind)) = BARE_KINDS.iter().find(|(name, _)| *name == arg) {
+        return Ok(Target::new(*kind));
+    }
+
+    if arg.contains(':') {
+        return parse_long_target(arg);
+    }
+
+    let error =
+        |start: usize, len: usize, message: String, suggestion: Option<String>| ParseError {
+            arg: arg.to_string(),
+            start,
+            len,
+            message,
+            suggestion,
+        };
+
+    let Some((target_name, mode_chars)) = arg.split_once('-') else {
+        let suggestion =
+            closest_kind(arg).map(|kind| match BARE_KINDS.iter().any(|(k, _)| *k == kind) {
+                true => kind.to_string(),
+                false => format!("{kind}-rs"),
+            });
+        return Err(error(
+            arg.len(),
+            0,
+            format!("missing '-' in target '{arg}'"),
+            suggestion,
+        ));
+    };
+    let kind = KINDS
+        .iter()
+        .find(|(name, _)| *name == target_name)
+        .map(|(_, kind)| *kind);
+    let Some(kind) = kind else {
+        if BARE_KINDS.iter().any(|(name, _)| *name == target_name) {
+            return Err(error(
+                target_name.len(),
+                arg.len() - target_name.len(),
+                format!("'{target_name}' takes no direction"),
+                Some(target_name.to_string()),
+            ));
+        }
+        return Err(error(
+            0,
+            target_name.len(),
+            format!("unknown target kind '{target_name}'"),
+            closest_kind(target_name).map(str::to_string),
+        ));
+    };
+
+    let offset = target_name.len() + 1;
+    let mut chars = mode_chars.char_indices();
+    let (backward, vertical) = match chars.next() {
+        Some((_, c)) => match DIRECTIONS.iter().find(|(d, _)| *d == c) {
+            Some((_, dir)) => *dir,
+            None => {
+                return Err(error(
+                    offset,
+                    c.len_utf8(),
+                    format!(
+                        "unknown direction '{c}', expected one of {}",
+                        list(DIRECTIONS)
+                    ),
+                    None,
+                ))
+            }
+        },
+        None => {
+            return Err(error(
+                offset,
+                0,
+                format!("missing direction, expected one of {}", list(DIRECTIONS)),
+                None,
+            ))
+        }
+    };
+    let edge_mode = match chars.next() {
+        Some((i, c)) => match EDGE_MODES.iter().find(|(e, _)| *e == c) {
+            Some((_, edge_mode)) => *edge_mode,
+            None => {
+                return Err(error(
+                    offset + i,
+                    c.len_utf8(),
+                    format!(
+                        "unknown edge action '{c}', expected one of {}",
+                        list(EDGE_MODES)
+                    ),
+                    None,
+                ))
+            }
+        },
+        None => {
+            return Err(error(
+                arg.len(),
+                0,
+                format!("missing edge action, expected one of {}", list(EDGE_MODES)),
+                None,
+            ))
+        }
+    };
+    if let Some((i, _)) = chars.next() {
+        let start = offset + i;
+        return Err(error(
+            start,
+            arg.len() - start,
+            "unexpected trailing characters".to_string(),
+            Some(arg[..start].to_string()),
+        ));
+    }
+
+    Ok(Target {
+        kind,
+        backward,
+        vertical,
+        edge_mode,
+        min_children: 0,
+        distinct_apps: false,
+    })
+}
+
+/// Parse a target of the form `<kind>:<direction>:<edge mode>`, using full names.
+fn parse_long_target(arg: &str) -> Result<Target, ParseError> {
+    let error = |start: usize, len: usize, message: String| ParseError {
+        arg: arg.to_string(),
+        start,
+        len,
+        message,
+        suggestion: None,
+    };
+    let mut fields = arg.split(':');
+    let mut offset = 0;
+    // Takes the next field along with its byte offset
+    let mut next = || {
+        let field = fields.next()?;
+        let start = offset;
+        offset += field.len() + 1;
+        Some((start, field))
+    };
+
+    let (_, target_name) = next().unwrap_or_default();
+    let Some((_, kind)) = KINDS.iter().find(|(name, _)| *name == target_name) else {
+        if BARE_KINDS.iter().any(|(name, _)| *name == target_name) {
+            return Err(ParseError {
+                suggestion: Some(target_name.to_string()),
+                ..error(
+                    target_name.len(),
+                    arg.len() - target_name.len(),
+                    format!("'{target_name}' takes no direction"),
+                )
+            });
+        }
+        return Err(ParseError {
+            suggestion: closest_kind(target_name).map(str::to_string),
+            ..error(
+                0,
+                target_name.len(),
+                format!("unknown target kind '{target_name}'"),
+            )
+        });
+    };
+    let (start, dir_name) = next().unwrap_or((arg.len(), ""));
+    let dir = match DIRECTION_NAMES.iter().find(|(name, _)| *name == dir_name) {
+        Some((_, c)) => DIRECTIONS.iter().find(|(d, _)| d == c).map(|(_, d)| *d),
+        None => None,
+    };
+    let Some((backward, vertical)) = dir else {
+        let expected = names(DIRECTION_NAMES);
+        return Err(match dir_name {
+            "" => error(
+                start,
+                0,
+                format!("missing direction, expected one of {expected}"),
+            ),
+            name => error(
+                start,
+                name.len(),
+                format!("unknown direction '{name}', expected one of {expected}"),
+            ),
+        });
+    };
+    let (start, edge) = next().unwrap_or((arg.len(), ""));
+    let edge_mode = match EDGE_MODE_NAMES.iter().find(|(name, _)| *name == edge) {
+        Some((_, c)) => EDGE_MODES.iter().find(|(e, _)| e == c).map(|(_, e)| *e),
+        None => None,
+    };
+    let Some(edge_mode) = edge_mode else {
+        let expected = names(EDGE_MODE_NAMES);
+        return Err(match edge {
+            "" => error(
+                start,
+                0,
+                format!("missing edge action, expected one of {expected}"),
+            ),
+            name => error(
+                start,
+                name.len(),
+                format!("unknown edge action '{name}', expected one of {expected}"),
+            ),
+        });
+    };
+    if let Some((start, _)) = next() {
+        return Err(ParseError {
+            suggestion: Some(arg[..start - 1].to_string()),
+            ..error(
+                start - 1,
+                arg.len() + 1 - start,
+                "unexpected trailing characters".to_string(),
+            )
+        });
+    }
+
+    Ok(Target {
+        kind: *kind,
+        backward,
+        vertical,
+        edge_mode,
+        min_children: 0,
+        distinct_apps: false,
+    })
+}
+
+/// Comma-separated list of valid names.
+fn names(options: &[(&str, char)]) -> String {
+    let names: Vec<&str> = options.iter().map(|(name, _)| *name).collect();
+    names.join(", ")
+}
+
+/// Comma-separated list of valid characters.
+fn list<T>(options: &[(char, T)]) -> String {
+    let chars: Vec<String> = options.iter().map(|(c, _)| c.to_string()).collect();
+    chars.join(", ")
+}
+
+/// Find the kind name closest to `name`, if it is reasonably close.
+fn closest_kind(name: &str) -> Option<&'static str> {
+    KINDS
+        .iter()
+        .chain(BARE_KINDS)
+        .map(|(kind, _)| (edit_distance(name, kind), *kind))
+        .filter(|(dist, kind)| *dist <= kind.len() / 2)
+        .min_by_key(|(dist, _)| *dist)
+        .map(|(_, kind)| kind)
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(arg: &str) -> ParseError {
+        parse_target(arg).unwrap_err()
+    }
+
+    #[test]
+    fn edit_distance_counts_edits() {
+        assert_eq!(edit_distance("split", "split"), 0);
+        assert_eq!(edit_distance("splt", "split"), 1);
+        assert_eq!(edit_distance("sptil", "split"), 2);
+        assert_eq!(edit_distance("", "float"), 5);
+        assert_eq!(edit_distance("group", ""), 5);
+    }
+
+    #[test]
+    fn unknown_kind_suggests_closest() {
+        let e = error("splt-rw");
+        assert_eq!(e.message, "unknown target kind 'splt'");
+        assert_eq!((e.start, e.len), (0, 4));
+        assert_eq!(e.suggestion.as_deref(), Some("split"));
+    }
+
+    #[test]
+    fn unknown_kind_without_close_match() {
+        let e = error("xyzzy-rw");
+        assert_eq!(e.message, "unknown target kind 'xyzzy'");
+        assert_eq!(e.suggestion, None);
+    }
+
+    #[test]
+    fn bare_kind_with_direction() {
+        let e = error("dialog-rw");
+        assert_eq!(e.message, "'dialog' takes no direction");
+        assert_eq!((e.start, e.len), (6, 3));
+        assert_eq!(e.suggestion.as_deref(), Some("dialog"));
+    }
+
+    #[test]
+    fn missing_dash_suggests_target() {
+        let e = error("splti");
+        assert_eq!(e.message, "missing '-' in target 'splti'");
+        assert_eq!(e.suggestion.as_deref(), Some("split-rs"));
+        assert_eq!(error("dialgo").suggestion.as_deref(), Some("dialog"));
+    }
+
+    #[test]
+    fn invalid_direction_and_edge() {
+        let e = error("split-xw");
+        assert_eq!(
+            e.message,
+            "unknown direction 'x', expected one of u, d, l, r"
+        );
+        assert_eq!((e.start, e.len), (6, 1));
+        let e = error("split-rx");
+        assert!(e.message.starts_with("unknown edge action 'x'"));
+        assert_eq!((e.start, e.len), (7, 1));
+        let e = error("split-r");
+        assert!(e.message.starts_with("missing edge action"));
+    }
+
+    #[test]
+    fn bare_kind_with_long_direction() {
+        let e = error("layer:right:wrap");
+        assert_eq!(e.message, "'layer' takes no direction");
+        assert_eq!(e.suggestion.as_deref(), Some("layer"));
+    }
+
+    #[test]
+    fn flag_errors_quote_flags() {
+        let args = |s: &str| -> Vec<String> { s.split(' ').map(str::to_string).collect() };
+        let e = parse_targets(&args("--kind split --dir left")).unwrap_err();
+        assert_eq!(e.arg, "--kind split --dir left");
+        assert!(e.message.starts_with("missing edge action"));
+        assert_eq!((e.start, e.len), (23, 0));
+
+        let e = parse_targets(&args("--kind split --edge wrap --dir sideways")).unwrap_err();
+        assert!(e.message.starts_with("unknown direction 'sideways'"));
+        assert_eq!(&e.arg[e.start..e.start + e.len], "sideways");
+
+        let e = parse_targets(&args("--kind splt --dir left --edge wrap")).unwrap_err();
+        assert_eq!(&e.arg[e.start..e.start + e.len], "splt");
+        assert_eq!(e.suggestion.as_deref(), Some("split"));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for (name, _) in KINDS {
+            for dir in ["u", "d", "l", "r"] {
+                for edge in ["s", "w", "t", "i"] {
+                    let arg = format!("{name}-{dir}{edge}");
+                    assert_eq!(parse_target(&arg).unwrap().to_string(), arg);
+                }
+            }
+        }
+        for arg in [
+            "dialog",
+            "recent(distinct)",
+            "split-rw(min=2)",
+            "created-ls(min=3,distinct)",
+        ] {
+            let target = parse_target(arg).unwrap();
+            assert_eq!(target.to_string(), arg);
+            assert_eq!(parse_target(&target.to_string()).unwrap(), target);
+        }
+    }
+
+    #[test]
+    fn display_drops_direction_of_bare_kinds() {
+        let target = Target::new(Kind::Dialog)
+            .backward(true)
+            .edge_mode(EdgeMode::Wrap);
+        assert_eq!(target.to_string(), "dialog");
+        assert_eq!(parse_target("dialog").unwrap(), Target::new(Kind::Dialog));
+    }
+
+    #[test]
+    fn trailing_characters_suggest_prefix() {
+        let e = error("split-rww");
+        assert_eq!(e.message, "unexpected trailing characters");
+        assert_eq!(e.suggestion.as_deref(), Some("split-rw"));
+    }
+}
@@ -0,0 +1,252 @@
+//! Partial parsing of `get_tree` replies, leaving out the children of all workspaces but one.
+use log::{debug, trace};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+use swayipc_types::Node;
+
+/// Parse a `get_tree` reply, leaving out the children of workspaces other than `workspace`.
+/// The reply is first scanned for the children to leave out, which are then cut from it,
+/// so that the rest can be deserialized as usual.
+pub fn parse_tree_partial(payload: &[u8], workspace: i64) -> serde_json::Result<Node> {
+    debug!("Parsing tree, skipping workspaces other than {workspace}");
+    let mut skipped = Vec::new();
+    let mut deserializer = serde_json::Deserializer::from_slice(payload);
+    let seed = NodeSeed {
+        keep: workspace,
+        base: payload.as_ptr() as usize,
+        skipped: &mut skipped,
+    };
+    seed.deserialize(&mut deserializer)?;
+
+    let mut trimmed = Vec::with_capacity(payload.len());
+    let mut pos = 0;
+    skipped.sort_by_key(|range| range.start);
+    for range in skipped {
+        trimmed.extend_from_slice(&payload[pos..range.start]);
+        trimmed.extend_from_slice(b"[]");
+        pos = range.end;
+    }
+    trimmed.extend_from_slice(&payload[pos..]);
+    serde_json::from_slice(&trimmed)
+}
+
+/// A key of a node, borrowed from the reply unless it contains escapes.
+#[derive(Deserialize)]
+struct Key<'a>(#[serde(borrow)] Cow<'a, str>);
+
+/// Scans a node, collecting the byte ranges of the children of workspaces other than `keep`.
+struct NodeSeed<'a> {
+    keep: i64,
+    /// Address of the start of the reply.
+    base: usize,
+    skipped: &'a mut Vec<Range<usize>>,
+}
+
+impl NodeSeed<'_> {
+    fn reborrow(&mut self) -> NodeSeed<'_> {
+        NodeSeed {
+            keep: self.keep,
+            base: self.base,
+            skipped: &mut *self.skipped,
+        }
+    }
+
+    /// Handle a list of children that has been read as is.
+    /// Workspaces other than `keep` have it cut, other workspaces are left alone,
+    /// and the children of other nodes are scanned for workspaces.
+    fn children(
+        &mut self,
+        raw: &RawValue,
+        id: Option<i64>,
+        workspace: bool,
+    ) -> serde_json::Result<()> {
+        if workspace {
+            if id != Some(self.keep) {
+                trace!("Skipping children of workspace {id:?}");
+                let start = raw.get().as_ptr() as usize - self.base;
+                self.skipped.push(start..start + raw.get().len());
+            }
+            return Ok(());
+        }
+        let mut deserializer = serde_json::Deserializer::from_str(raw.get());
+        ChildrenSeed(self.reborrow()).deserialize(&mut deserializer)
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for NodeSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for NodeSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a tree node")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let mut id = None;
+        let mut node_type = None;
+        // Children that come before the ID or type, handled once both are known
+        let mut deferred: Vec<&RawValue> = Vec::new();
+        while let Some(Key(key)) = map.next_key()? {
+            match key.as_ref() {
+                "id" => id = Some(map.next_value::<i64>()?),
+                "type" => node_type = Some(map.next_value::<Key>()?.0 == "workspace"),
+                // Sway lists the ID and type first, so the children of other nodes are scanned in place
+                "nodes" | "floating_nodes" if id.is_some() && node_type == Some(false) => {
+                    map.next_value_seed(ChildrenSeed(self.reborrow()))?
+                }
+                "nodes" | "floating_nodes" if id.is_some() && node_type == Some(true) => {
+                    let raw: &RawValue = map.next_value()?;
+                    self.children(raw, id, true).map_err(de::Error::custom)?;
+                }
+                "nodes" | "floating_nodes" => deferred.push(map.next_value()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        for raw in deferred {
+            let workspace = node_type.unwrap_or(false);
+            self.children(raw, id, workspace)
+                .map_err(de::Error::custom)?;
+        }
+        Ok(())
+    }
+}
+
+/// Scans a list of nodes using a [NodeSeed].
+struct ChildrenSeed<'a>(NodeSeed<'a>);
+
+impl<'de> DeserializeSeed<'de> for ChildrenSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ChildrenSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a list of tree nodes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let ChildrenSeed(mut seed) = self;
+        while seq.next_element_seed(seed.reborrow())?.is_some() {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn node(id: i64, node_type: &str, nodes: Vec<Value>, floating_nodes: Vec<Value>) -> Value {
+        let rect = json!({"x": 0, "y": 0, "width": 0, "height": 0});
+        json!({
+            "id": id, "name": null, "type": node_type, "border": "none",
+            "current_border_width": 0, "layout": "splith", "percent": null,
+            "rect": rect, "window_rect": rect, "deco_rect": rect, "geometry": rect,
+            "urgent": false, "focused": false, "focus": [],
+            "nodes": nodes, "floating_nodes": floating_nodes, "sticky": false,
+        })
+    }
+
+    fn leaf(id: i64, node_type: &str) -> Value {
+        node(id, node_type, vec![], vec![])
+    }
+
+    /// Two outputs, one holding the scratchpad workspace with a floating window,
+    /// the other holding workspace 10 with a tiled and a floating window,
+    /// and workspace 20 with a nested split.
+    fn tree() -> Value {
+        let scratch = node(2, "workspace", vec![], vec![leaf(3, "floating_con")]);
+        let split = node(21, "con", vec![leaf(22, "con")], vec![]);
+        let workspaces = vec![
+            node(
+                10,
+                "workspace",
+                vec![leaf(11, "con")],
+                vec![leaf(12, "floating_con")],
+            ),
+            node(20, "workspace", vec![split], vec![]),
+        ];
+        let outputs = vec![
+            node(1, "output", vec![scratch], vec![]),
+            node(4, "output", workspaces, vec![]),
+        ];
+        node(0, "root", outputs, vec![])
+    }
+
+    /// Serialize `value` with the ID and type before the other keys of each node, like sway does.
+    fn sway_order(value: &Value) -> String {
+        match value {
+            Value::Object(map) => {
+                let first = ["id", "type"].into_iter().filter(|k| map.contains_key(*k));
+                let rest = map
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|k| !["id", "type"].contains(k));
+                let fields: Vec<String> = first
+                    .chain(rest)
+                    .map(|k| format!("{}:{}", json!(k), sway_order(&map[k])))
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            }
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(sway_order).collect();
+                format!("[{}]", items.join(","))
+            }
+            _ => value.to_string(),
+        }
+    }
+
+    /// Fully parse `value`, with the children of workspaces other than `keep` emptied.
+    fn expected(mut value: Value, keep: i64) -> Node {
+        fn empty(value: &mut Value, keep: i64) {
+            if value["type"] == "workspace" && value["id"] != keep {
+                value["nodes"] = json!([]);
+                value["floating_nodes"] = json!([]);
+            }
+            for key in ["nodes", "floating_nodes"] {
+                for child in value[key].as_array_mut().unwrap() {
+                    empty(child, keep);
+                }
+            }
+        }
+        empty(&mut value, keep);
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn partial_parse_matches_emptied_full_parse() {
+        for keep in [2, 10, 20, 99] {
+            let partial = parse_tree_partial(sway_order(&tree()).as_bytes(), keep).unwrap();
+            assert_eq!(partial, expected(tree(), keep), "keeping {keep}");
+        }
+    }
+
+    #[test]
+    fn partial_parse_handles_children_before_id_and_type() {
+        // Keys are sorted, so `floating_nodes` comes before `id`, and `nodes` before `type`
+        let payload = tree().to_string();
+        assert!(payload.find("\"nodes\"") < payload.find("\"type\""));
+        for keep in [2, 10, 20, 99] {
+            let partial = parse_tree_partial(payload.as_bytes(), keep).unwrap();
+            assert_eq!(partial, expected(tree(), keep), "keeping {keep}");
+        }
+    }
+}
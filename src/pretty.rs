@@ -0,0 +1,70 @@
+//! Indented text rendering of the tree.
+use crate::tree::{describe, focus_path};
+use std::fmt::Write;
+use swayipc_types::{Node, NodeLayout};
+
+/// Nodes to mark in the rendering.
+struct Marks {
+    focus_path: Vec<i64>,
+    ancestor: Option<i64>,
+    neighbor: Option<i64>,
+}
+
+/// Render `tree` as an indented tree drawn with box-drawing characters.
+/// Nodes on the focus path are marked with `*`, and the ancestor that the search ended at
+/// and the chosen neighbor are labeled, if given.
+pub fn render(tree: &Node, ancestor: Option<i64>, neighbor: Option<i64>) -> String {
+    let marks = Marks {
+        focus_path: focus_path(tree).iter().map(|n| n.id).collect(),
+        ancestor,
+        neighbor,
+    };
+    let mut out = String::new();
+    render_node(&mut out, tree, "", "", false, &marks);
+    out
+}
+
+/// Append the line for `node`, then the lines of its descendants.
+/// `prefix` starts the line of `node` and `indent` those of its children.
+fn render_node(
+    out: &mut String,
+    node: &Node,
+    prefix: &str,
+    indent: &str,
+    floating: bool,
+    marks: &Marks,
+) {
+    write!(out, "{prefix}{}", describe(node)).unwrap();
+    if node.layout != NodeLayout::None {
+        write!(out, " {:?}", node.layout).unwrap();
+    }
+    if floating {
+        out.push_str(" (floating)");
+    }
+    if marks.focus_path.contains(&node.id) {
+        out.push_str(" *");
+    }
+    if marks.ancestor == Some(node.id) {
+        out.push_str(" [ancestor]");
+    }
+    if marks.neighbor == Some(node.id) {
+        out.push_str(" [neighbor]");
+    }
+    out.push('\n');
+
+    let children: Vec<(&Node, bool)> = (node.nodes.iter().map(|n| (n, false)))
+        .chain(node.floating_nodes.iter().map(|n| (n, true)))
+        .collect();
+    for (i, (child, floating)) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (branch, cont) = if last { ("└─ ", "   ") } else { ("├─ ", "│  ") };
+        render_node(
+            out,
+            child,
+            &format!("{indent}{branch}"),
+            &format!("{indent}{cont}"),
+            *floating,
+            marks,
+        );
+    }
+}
@@ -0,0 +1,32 @@
+//! Timing of the stages of a navigation.
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Names of completed stages along with the time they completed.
+static LAPS: Mutex<Vec<(&str, Instant)>> = Mutex::new(Vec::new());
+
+/// Start timing, discarding any earlier stages.
+pub fn start() {
+    let mut laps = LAPS.lock().unwrap();
+    laps.clear();
+    laps.push(("start", Instant::now()));
+}
+
+/// Mark the end of the stage `name`.
+pub fn lap(name: &'static str) {
+    LAPS.lock().unwrap().push((name, Instant::now()));
+}
+
+/// Print the duration of each stage to stderr.
+pub fn report() {
+    let laps = LAPS.lock().unwrap();
+    for pair in laps.windows(2) {
+        let ((_, start), (name, end)) = (pair[0], pair[1]);
+        let ms = (end - start).as_secs_f64() * 1000.0;
+        eprintln!("{name:>20}: {ms:8.3} ms");
+    }
+    if let (Some((_, start)), Some((_, end))) = (laps.first(), laps.last()) {
+        let ms = (*end - *start).as_secs_f64() * 1000.0;
+        eprintln!("{:>20}: {ms:8.3} ms", "total");
+    }
+}
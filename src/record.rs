@@ -0,0 +1,77 @@
+//! Recording and replaying of navigation cases.
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use swayipc::Node;
+
+/// A recorded navigation.
+pub struct Case {
+    /// Flags and targets, as given on the command line.
+    pub args: Vec<String>,
+    /// The pre-processed tree that the targets were resolved in.
+    pub tree: Node,
+    /// The resulting focus command, if any.
+    pub command: Option<String>,
+}
+
+/// Write a case to a new file in `dir`, named after the current time.
+pub fn save(
+    dir: &Path,
+    args: &[String],
+    tree: &Node,
+    command: Option<&str>,
+) -> io::Result<PathBuf> {
+    let time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let path = dir.join(format!("{time}.json"));
+    let case = json!({ "args": args, "tree": tree, "command": command });
+    fs::create_dir_all(dir)?;
+    fs::write(&path, case.to_string())?;
+    Ok(path)
+}
+
+/// Load all cases in `dir`, sorted by file name.
+pub fn load(dir: &Path) -> io::Result<Vec<(String, Case)>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|p| p.extension().is_some_and(|ext| ext == "json"));
+    paths.sort();
+
+    let mut cases = Vec::new();
+    for path in paths {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        cases.push((name, load_case(&path)?));
+    }
+    Ok(cases)
+}
+
+/// Load a single case from the file at `path`.
+pub fn load_case(path: &Path) -> io::Result<Case> {
+    let mut value: Value = serde_json::from_slice(&fs::read(path)?)?;
+    let invalid = || {
+        let msg = format!("invalid case {}", path.display());
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    };
+    let args = serde_json::from_value(value["args"].take()).map_err(|_| invalid())?;
+    let tree = serde_json::from_value(value["tree"].take()).map_err(|_| invalid())?;
+    let command = serde_json::from_value(value["command"].take()).map_err(|_| invalid())?;
+    Ok(Case {
+        args,
+        tree,
+        command,
+    })
+}
+
+/// Serialize a case in the format it is saved in.
+pub fn to_json(case: &Case) -> Value {
+    json!({ "args": case.args, "tree": case.tree, "command": case.command })
+}
@@ -1,7 +1,9 @@
 //! Basic tree functions and pre-processing
 use log::{debug, trace};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
 use std::mem;
-use swayipc::{Node, NodeLayout, NodeType, Rect};
+use swayipc_types::{Node, NodeLayout, NodeType, Rect};
 
 /// Closest point to `p` within `rect`.
 pub fn closest_point(rect: &Rect, p: &Vec2) -> Vec2 {
@@ -11,44 +13,290 @@ pub fn closest_point(rect: &Rect, p: &Vec2) -> Vec2 {
     }
 }
 
+/// Center point of `rect`.
+pub fn center(rect: &Rect) -> Vec2 {
+    Vec2 {
+        x: rect.x + rect.width / 2,
+        y: rect.y + rect.height / 2,
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Vec2 {
     pub x: i32,
     pub y: i32,
 }
 
+impl Vec2 {
+    /// Squared euclidean distance to `other`.
+    pub fn sq_dist(&self, other: &Vec2) -> i64 {
+        let (dx, dy) = ((self.x - other.x) as i64, (self.y - other.y) as i64);
+        dx * dx + dy * dy
+    }
+}
+
+/// Options for generating focus commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandOptions {
+    /// Focus workspaces with a numeric prefix by number rather than by full name.
+    pub numbered: bool,
+    /// Keep `workspace_auto_back_and_forth` from applying to workspace commands.
+    pub no_auto_back_and_forth: bool,
+}
+
 /// Generate a command that will focus `node`.
-pub fn focus_command(node: &Node) -> Option<String> {
+/// When `node` is a workspace or output, the command also focuses its most recently focused
+/// window, so the result doesn't depend on how sway restores focus.
+pub fn focus_command(node: &Node, options: &CommandOptions) -> Option<String> {
     let name = node.name.clone();
+    let cmd = match node.node_type {
+        NodeType::Root => return None,
+        NodeType::Output => format!("focus output {}", name?),
+        NodeType::Workspace => match workspace_number(name.as_deref()?) {
+            Some(num) if options.numbered => workspace_command(&num.to_string(), true, options),
+            _ => workspace_command(&name?, false, options),
+        },
+        _ => return Some(format!("[con_id={}] focus", node.id)),
+    };
+    let leaf = focused_leaf(node);
+    match leaf.node_type {
+        NodeType::Con | NodeType::FloatingCon => Some(format!("{cmd}; [con_id={}] focus", leaf.id)),
+        _ => Some(cmd),
+    }
+}
+
+/// Generate a command that switches to the workspace `name`, or number `name` if `number` is set.
+pub fn workspace_command(name: &str, number: bool, options: &CommandOptions) -> String {
+    let mut cmd = "workspace".to_string();
+    if options.no_auto_back_and_forth {
+        cmd.push_str(" --no-auto-back-and-forth");
+    }
+    if number {
+        cmd.push_str(" number");
+    }
+    format!("{cmd} {name}")
+}
+
+/// Mark used for moving a container next to another.
+const MOVE_MARK: &str = "_overfocus_move";
+
+/// Generate a command that moves the container `moved` to `node`.
+/// Containers are placed after `node` when moving forward, and before it when moving `backward`.
+pub fn move_command(moved: &Node, node: &Node, backward: bool) -> Option<String> {
+    let name = node.name.clone();
+    let target = match node.node_type {
+        NodeType::Root => return None,
+        NodeType::Output => format!("output {}", name?),
+        NodeType::Workspace => format!("workspace {}", name?),
+        _ => {
+            // Moving to a mark places the container after the marked one,
+            // so moving backward swaps them afterwards
+            let mut cmd = format!(
+                "[con_id={}] mark --add {MOVE_MARK}; \
+                 [con_id={}] move container to mark {MOVE_MARK}; \
+                 unmark {MOVE_MARK}",
+                node.id, moved.id,
+            );
+            if backward {
+                write!(
+                    cmd,
+                    "; [con_id={}] swap container with con_id {}",
+                    moved.id, node.id
+                )
+                .unwrap();
+            }
+            return Some(cmd);
+        }
+    };
+    Some(format!("[con_id={}] move container to {target}", moved.id))
+}
+
+/// Find the focused container, or its parent if `parent` is set.
+/// Only windows and other containers are returned, never workspaces.
+pub fn focused_container(tree: &Node, parent: bool) -> Option<&Node> {
+    let focused = tree.find_as_ref(|n| n.focused)?;
+    let res = match parent {
+        true => tree.find_as_ref(|n| {
+            n.nodes
+                .iter()
+                .chain(&n.floating_nodes)
+                .any(|c| c.id == focused.id)
+        })?,
+        false => focused,
+    };
+    matches!(res.node_type, NodeType::Con | NodeType::FloatingCon).then_some(res)
+}
+
+/// Numeric prefix of a workspace name, as interpreted by `workspace number`.
+fn workspace_number(name: &str) -> Option<u32> {
+    let digits = name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len());
+    name[..digits].parse().ok()
+}
+
+/// Short human-readable description of `node`.
+pub fn describe(node: &Node) -> String {
+    let name = node.name.as_deref().unwrap_or("");
     match node.node_type {
-        NodeType::Root => None,
-        NodeType::Output => Some(format!("focus output {}", name?)),
-        NodeType::Workspace => Some(format!("workspace {}", name?)),
-        _ => Some(format!("[con_id={}] focus", node.id)),
+        NodeType::Root => "root".to_string(),
+        NodeType::Output => format!("output '{name}'"),
+        NodeType::Workspace => format!("workspace '{name}'"),
+        _ => format!("con_id {} '{name}'", node.id),
     }
 }
 
+/// The application of a window: its `app_id`, or its X11 class under Xwayland.
+pub fn app(node: &Node) -> Option<&str> {
+    node.app_id.as_deref().or_else(|| {
+        let properties = node.window_properties.as_ref()?;
+        properties.class.as_deref()
+    })
+}
+
 /// Return the focused child, if any.
+/// The focus array is searched in order, so entries that don't refer to a child are skipped.
 pub fn focus_local(node: &Node) -> Option<&Node> {
-    let focus = *node.focus.first()?;
+    focus_idx(node).map(|(idx, children)| &children[idx])
+}
+
+/// Follow the focus path from `node` down to a leaf.
+pub fn focused_leaf(mut node: &Node) -> &Node {
+    while let Some(child) = focus_local(node) {
+        node = child;
+    }
+    node
+}
+
+/// Focus the node `id` within the subtree of `node`, as if sway had focused it,
+/// updating the focus arrays of its ancestors. Returns whether the node was found.
+pub fn refocus(node: &mut Node, id: i64) -> bool {
+    let mut found = node.id == id;
+    node.focused = found;
+    for child in node.nodes.iter_mut().chain(node.floating_nodes.iter_mut()) {
+        if refocus(child, id) {
+            found = true;
+            node.focus.retain(|f| *f != child.id);
+            node.focus.insert(0, child.id);
+        }
+    }
+    found
+}
+
+/// Remove the outputs named in `names` from the tree, unless they contain the focused node.
+pub fn remove_outputs(root: &mut Node, names: &[String]) {
+    let removed = |o: &Node| {
+        let named = names.iter().any(|name| o.name.as_ref() == Some(name));
+        named && o.find_as_ref(|n| n.focused).is_none()
+    };
+    let ids: Vec<i64> = root
+        .nodes
+        .iter()
+        .filter(|o| removed(o))
+        .map(|o| o.id)
+        .collect();
+    for id in ids {
+        debug!("Removing output {id}, which is turned off");
+        remove_node(root, id);
+    }
+}
+
+/// Remove the descendant `id` of `node` along with its subtree.
+/// Returns whether it was found.
+pub fn remove_node(node: &mut Node, id: i64) -> bool {
+    let len = node.nodes.len() + node.floating_nodes.len();
+    node.nodes.retain(|n| n.id != id);
+    node.floating_nodes.retain(|n| n.id != id);
+    if node.nodes.len() + node.floating_nodes.len() < len {
+        node.focus.retain(|f| *f != id);
+        return true;
+    }
+    let mut children = node.nodes.iter_mut().chain(node.floating_nodes.iter_mut());
+    children.any(|child| remove_node(child, id))
+}
+
+/// Collect the nodes from `tree` down to the focused node, both included.
+pub fn focus_path(tree: &Node) -> Vec<&Node> {
+    let mut path = vec![tree];
+    let mut node = tree;
+    while let Some(child) = focus_local(node).filter(|_| !node.focused) {
+        path.push(child);
+        node = child;
+    }
+    path
+}
+
+/// Whether `node` is a container that only wraps a single child,
+/// or that has the same layout as its parent.
+pub fn is_wrapper(node: &Node, parent: &Node) -> bool {
+    let children = node.nodes.len() + node.floating_nodes.len();
+    node.node_type == NodeType::Con
+        && children > 0
+        && (children == 1 || node.layout == parent.layout)
+}
+
+/// Collect all leaves of `node`, tiled and floating.
+pub fn leaves(node: &Node) -> Vec<&Node> {
+    if node.nodes.is_empty() && node.floating_nodes.is_empty() {
+        return vec![node];
+    }
     node.nodes
         .iter()
-        .chain(node.floating_nodes.iter())
-        .find(|child| child.id == focus)
+        .chain(&node.floating_nodes)
+        .flat_map(leaves)
+        .collect()
+}
+
+/// Collect the tiled leaves of `node` that are visible,
+/// meaning only the focused child of tabbed and stacked containers is included.
+pub fn visible_leaves(node: &Node) -> Vec<&Node> {
+    if node.nodes.is_empty() {
+        return vec![node];
+    }
+    match node.layout {
+        NodeLayout::Tabbed | NodeLayout::Stacked => {
+            focus_local(node).map(visible_leaves).unwrap_or_default()
+        }
+        _ => node.nodes.iter().flat_map(visible_leaves).collect(),
+    }
 }
 
 /// Compute the index (_not_ identifier) of the focused node in child array, if any.
 /// Also returns the vector of children to index into (either regular nodes or floats).
+/// The entire focus array is searched in order,
+/// so the most recently focused node that is still a child is selected.
+/// If no entry refers to a child, the child containing the focused node is selected instead,
+/// as the focus array can be stale while containers are being moved.
 pub fn focus_idx(node: &Node) -> Option<(usize, &Vec<Node>)> {
-    let focus = *node.focus.first()?;
-    for children in [&node.nodes, &node.floating_nodes] {
-        for (index, child) in children.iter().enumerate() {
-            if child.id == focus {
-                return Some((index, children));
-            }
-        }
+    let find = |pred: &dyn Fn(&Node) -> bool| {
+        [&node.nodes, &node.floating_nodes]
+            .into_iter()
+            .find_map(|children| Some((children.iter().position(pred)?, children)))
+    };
+    node.focus
+        .iter()
+        .find_map(|focus| find(&|child| child.id == *focus))
+        .or_else(|| find(&|child| child.find_as_ref(|n| n.focused).is_some()))
+}
+
+/// Remove containers marked with `mark` from the subtree of `node`,
+/// unless they contain the focused node. Containers left without children are removed as well.
+/// Returns whether `node` itself should be removed.
+pub fn remove_marked(node: &mut Node, mark: &str) -> bool {
+    let had_children = !node.nodes.is_empty() || !node.floating_nodes.is_empty();
+    node.nodes.retain_mut(|n| !remove_marked(n, mark));
+    node.floating_nodes.retain_mut(|n| !remove_marked(n, mark));
+    if !matches!(node.node_type, NodeType::Con | NodeType::FloatingCon) {
+        return false;
+    }
+    let emptied = had_children && node.nodes.is_empty() && node.floating_nodes.is_empty();
+    let marked = node.marks.iter().any(|m| m == mark);
+    let remove = emptied || marked && node.find_as_ref(|n| n.focused).is_none();
+    if remove {
+        trace!("Removing container {}", node.id);
     }
-    None
+    remove
 }
 
 /// Reform the tree to prepare for neighbor searching
@@ -63,7 +311,7 @@ pub fn preprocess(mut node: Node) -> Node {
     for output in node.nodes.iter_mut() {
         debug!(
             "Output '{}', ID {}",
-            output.name.as_ref().unwrap_or(&"".to_string()),
+            output.name.as_deref().unwrap_or(""),
             output.id,
         );
 
@@ -72,7 +320,7 @@ pub fn preprocess(mut node: Node) -> Node {
         if let Some(content) = output
             .nodes
             .iter_mut()
-            .find(|node| node.name.as_ref() == Some(&"content".to_string()))
+            .find(|node| node.name.as_deref() == Some("content"))
         {
             trace!("Found 'content' subnode, collapsing");
             output.focus = mem::take(&mut content.focus);
@@ -83,7 +331,7 @@ pub fn preprocess(mut node: Node) -> Node {
         for workspace in output.nodes.iter_mut() {
             debug!(
                 "Workspace '{}', ID {}",
-                workspace.name.as_ref().unwrap_or(&"".to_string()),
+                workspace.name.as_deref().unwrap_or(""),
                 workspace.id,
             );
             // Collapse nodes with fullscreen descendants
@@ -99,9 +347,9 @@ pub fn preprocess(mut node: Node) -> Node {
                     return fullscreen_node;
                 }
                 // Otherwise, it replaces the workspace
-                if output.focus.first() == Some(&workspace.id) {
-                    // We may potentially have to change parent focus
-                    output.focus = vec![fullscreen_node.id];
+                // The workspace is replaced in the focus stack of the output as well
+                for id in output.focus.iter_mut().filter(|id| **id == workspace.id) {
+                    *id = fullscreen_node.id;
                 }
                 *workspace = fullscreen_node;
             }
@@ -110,18 +358,231 @@ pub fn preprocess(mut node: Node) -> Node {
     node
 }
 
+/// Replace split containers that have a single child with that child, throughout the tree.
+/// Such chains are left behind by autotiling scripts, and hide the effective layout.
+/// Focused containers are kept, as the focus path would otherwise run past them.
+pub fn collapse_chains(node: &mut Node) {
+    for child in node.nodes.iter_mut() {
+        while matches!(child.layout, NodeLayout::SplitH | NodeLayout::SplitV)
+            && child.node_type == NodeType::Con
+            && !child.focused
+            && child.nodes.len() == 1
+            && child.floating_nodes.is_empty()
+        {
+            trace!("Collapsing single-child container {}", child.id);
+            let grandchild = child.nodes.pop().unwrap();
+            for id in node.focus.iter_mut().filter(|id| **id == child.id) {
+                *id = grandchild.id;
+            }
+            *child = grandchild;
+        }
+    }
+    for child in node.nodes.iter_mut().chain(node.floating_nodes.iter_mut()) {
+        collapse_chains(child);
+    }
+}
+
 /// Search the tree for a fullscreen descendant.
 /// If found, the descendant is detached and returned.
 /// Neighbors of the descendant are detached and dropped as collateral.
 pub fn extract_fullscreen_child(node: &mut Node) -> Option<Node> {
-    let mut children = node.nodes.iter_mut().chain(node.floating_nodes.iter_mut());
     let pred = |child: &Node| child.fullscreen_mode == Some(1) || child.fullscreen_mode == Some(2);
-    if children.any(|c| pred(c)) {
-        let nodes = mem::take(&mut node.nodes);
-        let floating_nodes = mem::take(&mut node.floating_nodes);
-        let mut children = nodes.into_iter().chain(floating_nodes.into_iter());
-        children.find(pred)
-    } else {
-        node.nodes.iter_mut().find_map(extract_fullscreen_child)
+
+    // Find the path of tiled child indices leading to the parent of the fullscreen node.
+    // The direct children of a node are checked before descending into them.
+    let mut path = Vec::new();
+    let mut stack = vec![(&*node, 0, 0)];
+    loop {
+        let (t, depth, idx) = stack.pop()?;
+        if depth > 0 {
+            path.truncate(depth - 1);
+            path.push(idx);
+        }
+        if t.nodes.iter().chain(&t.floating_nodes).any(pred) {
+            break;
+        }
+        stack.extend(
+            t.nodes
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, c)| (c, depth + 1, i)),
+        );
+    }
+
+    let mut parent = node;
+    for idx in path {
+        parent = &mut parent.nodes[idx];
+    }
+    let child = match parent.nodes.iter().position(pred) {
+        Some(idx) => parent.nodes.swap_remove(idx),
+        None => {
+            let idx = parent.floating_nodes.iter().position(pred)?;
+            parent.floating_nodes.swap_remove(idx)
+        }
+    };
+    parent.nodes.clear();
+    parent.floating_nodes.clear();
+    Some(child)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::{neighbor, Kind, Options, Target};
+
+    use serde_json::json;
+
+    /// A node without children or window properties.
+    fn node(id: i64, node_type: NodeType, layout: NodeLayout) -> Node {
+        let rect = json!({"x": 0, "y": 0, "width": 0, "height": 0});
+        serde_json::from_value(json!({
+            "id": id, "name": null, "type": node_type, "border": "none",
+            "current_border_width": 0, "layout": layout, "percent": null,
+            "rect": rect, "window_rect": rect, "deco_rect": rect, "geometry": rect,
+            "urgent": false, "focused": false, "focus": [],
+            "nodes": [], "floating_nodes": [], "sticky": false,
+        }))
+        .unwrap()
+    }
+
+    /// Give `parent` the `children`, focused in order.
+    fn with(mut parent: Node, children: Vec<Node>) -> Node {
+        parent.focus = children.iter().map(|n| n.id).collect();
+        parent.nodes = children;
+        parent
+    }
+
+    fn workspace(id: i64, num: i32, children: Vec<Node>) -> Node {
+        let mut w = node(id, NodeType::Workspace, NodeLayout::SplitH);
+        w.name = Some(num.to_string());
+        w.num = Some(num);
+        with(w, children)
+    }
+
+    /// An output with a focused window on workspace 1,
+    /// a window nested in a split on workspace 2, and an empty workspace 3.
+    fn tree() -> Node {
+        let mut focused = node(11, NodeType::Con, NodeLayout::None);
+        focused.focused = true;
+        let split = node(21, NodeType::Con, NodeLayout::SplitV);
+        let nested = with(split, vec![node(22, NodeType::Con, NodeLayout::None)]);
+        let workspaces = vec![
+            workspace(10, 1, vec![focused]),
+            workspace(20, 2, vec![nested]),
+            workspace(30, 3, vec![]),
+        ];
+        let output = with(node(1, NodeType::Output, NodeLayout::Output), workspaces);
+        with(node(0, NodeType::Root, NodeLayout::None), vec![output])
+    }
+
+    #[test]
+    fn workspace_focuses_recent_window() {
+        let tree = tree();
+        let ws = tree.find_as_ref(|n| n.id == 20).unwrap();
+        let cmd = focus_command(ws, &CommandOptions::default());
+        assert_eq!(cmd.as_deref(), Some("workspace 2; [con_id=22] focus"));
+    }
+
+    #[test]
+    fn empty_workspace_has_no_window() {
+        let tree = tree();
+        let ws = tree.find_as_ref(|n| n.id == 30).unwrap();
+        let cmd = focus_command(ws, &CommandOptions::default());
+        assert_eq!(cmd.as_deref(), Some("workspace 3"));
+    }
+
+    #[test]
+    fn collapse_chains_keeps_focused_container() {
+        // A split focused with `focus parent`, wrapping a tab group
+        let tabs = node(4, NodeType::Con, NodeLayout::Tabbed);
+        let tabs = with(
+            tabs,
+            (5..7)
+                .map(|id| node(id, NodeType::Con, NodeLayout::None))
+                .collect(),
+        );
+        let mut split = with(node(3, NodeType::Con, NodeLayout::SplitV), vec![tabs]);
+        split.focused = true;
+        let ws = workspace(2, 1, vec![split, node(7, NodeType::Con, NodeLayout::None)]);
+        let output = with(node(1, NodeType::Output, NodeLayout::Output), vec![ws]);
+        let mut tree = with(node(0, NodeType::Root, NodeLayout::None), vec![output]);
+        collapse_chains(&mut tree);
+
+        assert!(tree.find_as_ref(|n| n.id == 3).is_some_and(|n| n.focused));
+        let group = |backward| Target::new(Kind::Group).backward(backward);
+        assert_eq!(neighbor(&tree, &[group(false)], &Options::default()), None);
+        let up = group(true).vertical(true);
+        assert_eq!(neighbor(&tree, &[up], &Options::default()), None);
+    }
+
+    /// The recursive implementation that [extract_fullscreen_child] replaced.
+    fn extract_fullscreen_child_recursive(node: &mut Node) -> Option<Node> {
+        let mut children = node.nodes.iter_mut().chain(node.floating_nodes.iter_mut());
+        let pred = |child: &Node| matches!(child.fullscreen_mode, Some(1 | 2));
+        if children.any(|c| pred(c)) {
+            let nodes = mem::take(&mut node.nodes);
+            let floating_nodes = mem::take(&mut node.floating_nodes);
+            let mut children = nodes.into_iter().chain(floating_nodes);
+            children.find(pred)
+        } else {
+            node.nodes
+                .iter_mut()
+                .find_map(extract_fullscreen_child_recursive)
+        }
+    }
+
+    /// A tree of up to `depth` levels with a few fullscreen containers, generated from `seed`.
+    fn random_tree(seed: &mut u64, id: &mut i64, depth: usize) -> Node {
+        // xorshift, so the trees are the same on every run
+        let mut next = || {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 7;
+            *seed ^= *seed << 17;
+            *seed
+        };
+        *id += 1;
+        let mut node = node(*id, NodeType::Con, NodeLayout::SplitH);
+        if next() % 16 == 0 {
+            node.fullscreen_mode = Some(1 + (next() % 2) as u8);
+        }
+        if depth > 0 {
+            let (tiled, floating) = (next() % 4, next() % 4 == 0);
+            for _ in 0..tiled {
+                node.nodes.push(random_tree(seed, id, depth - 1));
+            }
+            if floating {
+                node.floating_nodes.push(random_tree(seed, id, depth - 1));
+            }
+        }
+        node
+    }
+
+    #[test]
+    fn extract_fullscreen_child_matches_recursive() {
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        let mut found = 0;
+        for depth in (0..8).cycle().take(400) {
+            let tree = random_tree(&mut seed, &mut 0, depth);
+            let (mut iterative, mut recursive) = (tree.clone(), tree);
+            let res = extract_fullscreen_child(&mut iterative);
+            assert_eq!(res, extract_fullscreen_child_recursive(&mut recursive));
+            assert_eq!(iterative, recursive);
+            found += usize::from(res.is_some());
+        }
+        // Both outcomes have to be covered
+        assert!(
+            (1..400).contains(&found),
+            "{found} of 400 trees had a fullscreen container"
+        );
+    }
+
+    #[test]
+    fn workspace_target_selects_window() {
+        let tree = tree();
+        let target = Target::new(Kind::Workspace);
+        let found = neighbor(&tree, &[target], &Options::default()).unwrap();
+        let cmd = focus_command(found, &CommandOptions::default());
+        assert_eq!(cmd.as_deref(), Some("[con_id=22] focus"));
     }
 }